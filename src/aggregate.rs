@@ -0,0 +1,400 @@
+use rustc_serialize::json::{self, ToJson};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use err::DataError;
+use ql2::TermType;
+use super::{TreeNode, Value, Queryable, Stream, Selection, SingleSelection, ReQLGenState, ClosureVar, Table, OptArgs, Selector, Func, Func2, Func3, make_term};
+use convert;
+
+//Group
+
+/// A sequence split into groups by one or more keys. Operations applied to it, such as `count`
+/// or `reduce`, run on each group separately, and produce grouped data that can be decoded with
+/// `Queryable::run_grouped` or turned back into a sequence with `ungroup`.
+pub struct Group<'a, S>
+	where S: Stream
+{
+	source: S,
+	keys: Vec<Box<TreeNode + 'a>>,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S> Group<'a, S>
+	where S: Stream
+{
+	pub fn new(source: S, keys: Vec<Box<TreeNode + 'a>>) -> Group<'a, S> {
+		Group {source: source, keys: keys, optargs: OptArgs::new()}
+	}
+	
+	/// Groups by the values of fields. A nested selector groups by each field it selects.
+	pub fn from_fields<I>(source: S, fields: I) -> Group<'a, S>
+		where I: IntoIterator, I::Item: Into<Selector>
+	{
+		let mut paths = Vec::new();
+		for field in fields {
+			field_paths(&field.into(), &mut Vec::new(), &mut paths);
+		}
+		
+		Group::new(source, paths.into_iter().map(|path| Box::new(FieldPath(path)) as Box<TreeNode + 'a>).collect())
+	}
+	
+	/// If a key is an array, put the element in a group for each of its values instead of one
+	/// group for the whole array
+	pub fn multi(mut self, val: bool) -> Group<'a, S> {
+		self.optargs.set("multi", val);
+		self
+	}
+}
+
+impl<'a, 'b> Group<'a, Table<'b>> {
+	/// Group a table by a secondary index. The keys may then be empty.
+	pub fn index(mut self, name: &str) -> Group<'a, Table<'b>> {
+		self.optargs.set("index", name.to_owned());
+		self
+	}
+}
+
+impl<'a> Table<'a> {
+	/// Groups the documents by the values of a secondary index
+	pub fn group_index<'b>(self, name: &str) -> Group<'b, Table<'a>> {
+		Group::new(self, Vec::new()).index(name)
+	}
+}
+
+/// Collects the path of field names to each field a selector selects
+fn field_paths(selector: &Selector, prefix: &mut Vec<String>, paths: &mut Vec<Vec<String>>) {
+	match *selector {
+		Selector::Field(ref name) => {
+			let mut path = prefix.clone();
+			path.push(name.clone());
+			paths.push(path);
+		},
+		Selector::Nested(ref name, ref fields) => {
+			prefix.push(name.clone());
+			for field in fields {
+				field_paths(field, prefix, paths);
+			}
+			prefix.pop();
+		},
+	}
+}
+
+/// A grouping key reading a field, which may be inside nested objects
+struct FieldPath(Vec<String>);
+
+impl TreeNode for FieldPath {
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		//top-level fields can be given by name; nested ones need a function
+		if self.0.len() == 1 {
+			return self.0[0].to_json();
+		}
+		
+		let var = state.gen_closure_var();
+		let body = self.0.iter().fold(var.get_reql_json(state), |obj, field| {
+			make_term(TermType::GET_FIELD, vec![obj, field.to_json()], None)
+		});
+		
+		make_term(TermType::FUNC, vec![
+			make_term(TermType::MAKE_ARRAY, vec![var.n.to_json()], None),
+			body
+		], None)
+	}
+}
+
+impl<'a, S> TreeNode for Group<'a, S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let mut args = vec![self.source.get_reql_json(state)];
+		args.extend(self.keys.iter().map(|k| k.get_reql_json(state)));
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::GROUP, args, optargs)
+	}
+}
+
+impl<'a, S> Stream for Group<'a, S> where S: Stream {}
+
+impl<'a, S> Queryable for Group<'a, S> where S: Stream {}
+
+//Ungroup
+
+/// Grouped data as a sequence of objects with `group` and `reduction` fields
+pub struct Ungroup<S>
+	where S: TreeNode
+{
+	source: S,
+}
+
+impl<S> Ungroup<S>
+	where S: TreeNode
+{
+	pub fn new(source: S) -> Ungroup<S> {
+		Ungroup {source: source}
+	}
+}
+
+impl<S> TreeNode for Ungroup<S>
+	where S: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		make_term(TermType::UNGROUP, vec![source], None)
+	}
+}
+
+impl<S> Stream for Ungroup<S> where S: TreeNode {}
+
+impl<S> Queryable for Ungroup<S> where S: TreeNode {}
+
+//Aggregate
+
+/// A single value computed from a sequence: the result of `count`, `sum` or `avg`
+pub struct Aggregate<'a, S>
+	where S: Stream
+{
+	term_type: TermType,
+	source: S,
+	arg: Option<Box<TreeNode + 'a>>,
+}
+
+impl<'a, S> Aggregate<'a, S>
+	where S: Stream
+{
+	pub fn new(term_type: TermType, source: S, arg: Option<Box<TreeNode + 'a>>) -> Aggregate<'a, S> {
+		Aggregate {term_type: term_type, source: source, arg: arg}
+	}
+}
+
+impl<'a, S> TreeNode for Aggregate<'a, S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let mut args = vec![self.source.get_reql_json(state)];
+		if let Some(ref arg) = self.arg {
+			args.push(arg.get_reql_json(state));
+		}
+		make_term(self.term_type, args, None)
+	}
+}
+
+impl<'a, S> Value for Aggregate<'a, S> where S: Stream {}
+
+impl<'a, S> Queryable for Aggregate<'a, S> where S: Stream {}
+
+value_ops!(['a, S: Stream,] Aggregate<'a, S>);
+
+//MinMax
+
+/// The element of a sequence with the smallest or largest value: the result of `min` or `max`.
+/// The element of a selection can still be updated.
+pub struct MinMax<'a, S>
+	where S: Stream
+{
+	term_type: TermType,
+	source: S,
+	arg: Option<Box<TreeNode + 'a>>,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S> MinMax<'a, S>
+	where S: Stream
+{
+	pub fn new(term_type: TermType, source: S, arg: Option<Box<TreeNode + 'a>>) -> MinMax<'a, S> {
+		MinMax {term_type: term_type, source: source, arg: arg, optargs: OptArgs::new()}
+	}
+}
+
+impl<'a> Table<'a> {
+	/// The document with the smallest value in a secondary index
+	pub fn min_index<'b>(self, name: &str) -> MinMax<'b, Table<'a>> {
+		let mut min = MinMax::new(TermType::MIN, self, None);
+		min.optargs.set("index", name.to_owned());
+		min
+	}
+	
+	/// The document with the largest value in a secondary index
+	pub fn max_index<'b>(self, name: &str) -> MinMax<'b, Table<'a>> {
+		let mut max = MinMax::new(TermType::MAX, self, None);
+		max.optargs.set("index", name.to_owned());
+		max
+	}
+}
+
+impl<'a, S> TreeNode for MinMax<'a, S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let mut args = vec![self.source.get_reql_json(state)];
+		if let Some(ref arg) = self.arg {
+			args.push(arg.get_reql_json(state));
+		}
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(self.term_type, args, optargs)
+	}
+}
+
+impl<'a, S> Value for MinMax<'a, S> where S: Stream {}
+
+impl<'a, S> SingleSelection for MinMax<'a, S> where S: Selection {}
+
+impl<'a, S> Queryable for MinMax<'a, S> where S: Stream {}
+
+value_ops!(['a, S: Stream,] MinMax<'a, S>);
+
+//Reduce
+
+/// A sequence combined into one value by repeatedly applying a two-parameter function
+pub struct Reduce<S, P>
+	where S: Stream
+{
+	source: S,
+	func: Func2<P>,
+}
+
+impl<S, P, T> Reduce<S, P>
+	where S: Stream, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode
+{
+	pub fn new(source: S, func: P) -> Reduce<S, P> {
+		Reduce {source: source, func: Func2::new(func)}
+	}
+}
+
+impl<S, P, T> TreeNode for Reduce<S, P>
+	where S: Stream, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let func = self.func.get_reql_json(state);
+		make_term(TermType::REDUCE, vec![source, func], None)
+	}
+}
+
+impl<S, P, T> Value for Reduce<S, P> where S: Stream, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode {}
+
+impl<S, P, T> Queryable for Reduce<S, P> where S: Stream, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode {}
+
+value_ops!([S: Stream, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode,] Reduce<S, P>);
+
+//Fold
+
+/// A sequence combined into one value in order, starting from `base`. Unlike `reduce` the
+/// function is applied left to right, and `emit` can turn the fold into a sequence.
+pub struct Fold<'a, S, B, P>
+	where S: Stream, B: Value
+{
+	source: S,
+	base: B,
+	func: Func2<P>,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S, B, P, T> Fold<'a, S, B, P>
+	where S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode
+{
+	pub fn new(source: S, base: B, func: P) -> Fold<'a, S, B, P> {
+		Fold {source: source, base: base, func: Func2::new(func), optargs: OptArgs::new()}
+	}
+	
+	/// Called with the accumulator, the element and the new accumulator after each step, and
+	/// returns an array of values to emit. The result of the fold becomes the emitted values.
+	pub fn emit<E, U>(mut self, func: E) -> FoldEmit<'a, S, B, P>
+		where E: Fn(ClosureVar, ClosureVar, ClosureVar) -> U + 'a, U: TreeNode + 'a
+	{
+		self.optargs.set("emit", Func3::new(func));
+		FoldEmit {fold: self}
+	}
+}
+
+impl<'a, S, B, P, T> TreeNode for Fold<'a, S, B, P>
+	where S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let base = self.base.get_reql_json(state);
+		let func = self.func.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::FOLD, vec![source, base, func], optargs)
+	}
+}
+
+impl<'a, S, B, P, T> Value for Fold<'a, S, B, P> where S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode {}
+
+impl<'a, S, B, P, T> Queryable for Fold<'a, S, B, P> where S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode {}
+
+value_ops!(['a, S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode,] Fold<'a, S, B, P>);
+
+/// A fold that emits a sequence, created by `Fold::emit`
+pub struct FoldEmit<'a, S, B, P>
+	where S: Stream, B: Value
+{
+	fold: Fold<'a, S, B, P>,
+}
+
+impl<'a, S, B, P, T> FoldEmit<'a, S, B, P>
+	where S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode
+{
+	/// Called with the final accumulator, and returns an array of values to emit at the end
+	pub fn final_emit<E, U>(mut self, func: E) -> FoldEmit<'a, S, B, P>
+		where E: Fn(ClosureVar) -> U + 'a, U: TreeNode + 'a
+	{
+		self.fold.optargs.set("final_emit", Func::<E>::new(func));
+		self
+	}
+}
+
+impl<'a, S, B, P, T> TreeNode for FoldEmit<'a, S, B, P>
+	where S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		self.fold.get_reql_json(state)
+	}
+}
+
+impl<'a, S, B, P, T> Stream for FoldEmit<'a, S, B, P> where S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode {}
+
+impl<'a, S, B, P, T> Queryable for FoldEmit<'a, S, B, P> where S: Stream, B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode {}
+
+//Grouped results
+
+/// Decodes a `GROUPED_DATA` result into a map of group key to the value for that group
+pub fn decode_grouped<K, V>(obj: json::Json) -> Result<BTreeMap<K, V>, DataError>
+	where K: DeserializeOwned + Ord, V: DeserializeOwned
+{
+	let malformed = || DataError::MalformedPacket(obj.clone());
+	
+	match obj.find("$reql_type$").and_then(|t| t.as_string()) {
+		Some("GROUPED_DATA") => (),
+		_ => return Err(malformed()),
+	}
+	
+	let pairs = try!(obj.find("data").and_then(|d| d.as_array()).ok_or_else(&malformed));
+	
+	pairs.iter().map(|pair| match *pair {
+		json::Json::Array(ref pair) if pair.len() == 2 => {
+			let group = try!(convert::from_json(pair[0].clone()));
+			let value = try!(convert::from_json(pair[1].clone()));
+			Ok((group, value))
+		},
+		_ => Err(malformed()),
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use rustc_serialize::json::Json;
+	use std::collections::BTreeMap;
+	use super::*;
+	
+	#[test]
+	fn test_decode_grouped() {
+		let obj = Json::from_str("{\"$reql_type$\": \"GROUPED_DATA\", \"data\": [[\"done\", 3], [\"todo\", 5]]}").unwrap();
+		let groups: BTreeMap<String, u64> = decode_grouped(obj).unwrap();
+		
+		assert_eq!(groups.len(), 2);
+		assert_eq!(groups["done"], 3);
+		assert_eq!(groups["todo"], 5);
+		
+		let obj = Json::from_str("[[\"done\", 3]]").unwrap();
+		assert!(decode_grouped::<String, u64>(obj).is_err());
+	}
+}
@@ -0,0 +1,280 @@
+use rustc_serialize::json;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::result::Result;
+use err::{QueryError, ConnectionError, DataError};
+use ql2::TermType;
+use super::{TreeNode, Queryable, ReQLGenState, ResultSet, ConnRef, OptArgs, Formats, make_term, start_query, open_cursor};
+use net::{Connection, Reconnecting, Transport, Socket};
+use write::Change;
+
+/// The kind of event, sent by changefeeds run with `include_types`
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum ChangeType {
+	Add,
+	Remove,
+	Change,
+	/// A document sent because the feed was run with `include_initial`
+	Initial,
+	/// A previously sent initial document that has since been removed
+	Uninitial,
+	State,
+}
+
+impl ChangeType {
+	pub fn from_name(name: &str) -> Option<ChangeType> {
+		match name {
+			"add" => Some(ChangeType::Add),
+			"remove" => Some(ChangeType::Remove),
+			"change" => Some(ChangeType::Change),
+			"initial" => Some(ChangeType::Initial),
+			"uninitial" => Some(ChangeType::Uninitial),
+			"state" => Some(ChangeType::State),
+			_ => None,
+		}
+	}
+}
+
+/// The status events sent by changefeeds run with `include_states`
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum FeedState {
+	/// The initial results are still being sent
+	Initializing,
+	/// All initial results have been sent, and only changes follow
+	Ready,
+}
+
+impl FeedState {
+	pub fn from_name(name: &str) -> Option<FeedState> {
+		match name {
+			"initializing" => Some(FeedState::Initializing),
+			"ready" => Some(FeedState::Ready),
+			_ => None,
+		}
+	}
+}
+
+//Changes
+
+pub struct Changes<'a, S>
+	where S: TreeNode
+{
+	source: S,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S> Changes<'a, S>
+	where S: TreeNode
+{
+	pub fn new(source: S) -> Changes<'a, S> {
+		Changes {source: source, optargs: OptArgs::new()}
+	}
+	
+	/// Combine multiple changes to the same document into one until the client asks for more
+	pub fn squash(mut self, val: bool) -> Changes<'a, S> {
+		self.optargs.set("squash", val);
+		self
+	}
+	
+	/// Combine multiple changes to the same document that arrive within `secs` seconds
+	pub fn squash_secs(mut self, secs: f64) -> Changes<'a, S> {
+		self.optargs.set("squash", secs);
+		self
+	}
+	
+	/// Send the current contents of the selection before any changes
+	pub fn include_initial(mut self, val: bool) -> Changes<'a, S> {
+		self.optargs.set("include_initial", val);
+		self
+	}
+	
+	/// Send status events with `state` set as the feed initializes and becomes ready
+	pub fn include_states(mut self, val: bool) -> Changes<'a, S> {
+		self.optargs.set("include_states", val);
+		self
+	}
+	
+	/// Send the positions of changed documents in an `order_by().limit()` feed
+	pub fn include_offsets(mut self, val: bool) -> Changes<'a, S> {
+		self.optargs.set("include_offsets", val);
+		self
+	}
+	
+	/// Set `change_type` on every event
+	pub fn include_types(mut self, val: bool) -> Changes<'a, S> {
+		self.optargs.set("include_types", val);
+		self
+	}
+	
+	/// The number of changes the server buffers for a slow client before sending an error
+	pub fn changefeed_queue_size(mut self, val: u64) -> Changes<'a, S> {
+		self.optargs.set("changefeed_queue_size", val);
+		self
+	}
+	
+	/// Starts the feed, yielding each event with its documents as JSON
+	pub fn run_feed<'c, C: Transport>(self, conn: &'c Connection<C>) -> Result<Feed<'c, json::Json, C>, QueryError> {
+		let results = try!(self.run(conn));
+		Ok(Feed {results: results, decode: Change::from_json})
+	}
+	
+	/// Starts the feed, deserializing the old and new values of each event into `T`
+	pub fn run_feed_as<'c, T: DeserializeOwned, C: Transport>(self, conn: &'c Connection<C>) -> Result<Feed<'c, T, C>, QueryError> {
+		let results = try!(self.run(conn));
+		Ok(Feed {results: results, decode: Change::decode})
+	}
+	
+	/// Starts a feed that subscribes again after the connection is reopened, yielding its
+	/// documents as JSON
+	pub fn run_resumable(self, conn: &Reconnecting) -> Result<ResumableFeed<json::Json>, QueryError> {
+		ResumableFeed::start(self, conn, Change::from_json)
+	}
+	
+	/// Starts a feed that subscribes again after the connection is reopened, deserializing the
+	/// old and new values of each event into `T`
+	pub fn run_resumable_as<T: DeserializeOwned>(self, conn: &Reconnecting) -> Result<ResumableFeed<T>, QueryError> {
+		ResumableFeed::start(self, conn, Change::decode)
+	}
+}
+
+impl<'a, S> TreeNode for Changes<'a, S>
+	where S: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::CHANGES, vec![source], optargs)
+	}
+}
+
+impl<'a, S> Queryable for Changes<'a, S> where S: TreeNode {}
+
+//Feed
+
+/// A cursor over the events of a changefeed.
+///
+/// The server holds each request for more results until there is a change to report, so the
+/// feed only ends when it is closed or fails, for example because the table was dropped. A read
+/// timeout set on the connection also applies while waiting for changes.
+pub struct Feed<'a, T, S: 'a + Transport = Socket> {
+	results: ResultSet<'a, S>,
+	decode: fn(&json::Json) -> Result<Change<T>, DataError>,
+}
+
+impl<'a, T, S: Transport> Feed<'a, T, S> {
+	/// The underlying cursor, for its notes
+	pub fn results(&self) -> &ResultSet<'a, S> {
+		&self.results
+	}
+	
+	/// Stops the feed
+	pub fn close(&mut self) -> Result<(), QueryError> {
+		self.results.close()
+	}
+}
+
+impl<'a, T, S: Transport> Iterator for Feed<'a, T, S> {
+	type Item = Result<Change<T>, QueryError>;
+	
+	fn next(&mut self) -> Option<Result<Change<T>, QueryError>> {
+		let decode = self.decode;
+		self.results.next().map(|result| result.and_then(|obj| {
+			decode(&obj).map_err(|e| QueryError::from(ConnectionError::Data(e)))
+		}))
+	}
+}
+
+//ResumableFeed
+
+/// An event from a `ResumableFeed`
+#[derive(Debug,Clone,PartialEq)]
+pub enum FeedEvent<T = json::Json> {
+	Change(Change<T>),
+	/// The connection dropped and the feed has subscribed again. Changes made while it was down
+	/// were missed, so the current contents of the selection follow as new values with no old
+	/// value, and should be reconciled with what was seen before.
+	Resynced,
+}
+
+/// A changefeed that survives the connection dropping, created by `Changes::run_resumable`.
+///
+/// When the connection fails, the next call to `next` reopens it with backoff and runs the
+/// feed again with `include_initial` set, then yields `FeedEvent::Resynced`. A read timeout
+/// subscribes again the same way on the connection that is still open. If reconnecting
+/// fails the error is yielded instead, and the following call tries again.
+pub struct ResumableFeed<T = json::Json> {
+	conn: Reconnecting,
+	/// The START query to subscribe again with, which always includes the initial values
+	resync_query: json::Json,
+	/// `None` while the feed is waiting to subscribe again
+	results: Option<ResultSet<'static, Socket>>,
+	decode: fn(&json::Json) -> Result<Change<T>, DataError>,
+	closed: bool,
+}
+
+impl<T> ResumableFeed<T> {
+	fn start<'a, S>(mut changes: Changes<'a, S>, conn: &Reconnecting, decode: fn(&json::Json) -> Result<Change<T>, DataError>) -> Result<ResumableFeed<T>, QueryError>
+		where S: TreeNode
+	{
+		let current = try!(conn.get());
+		let query = start_query(&changes, &current.default_db(), BTreeMap::new());
+		changes.optargs.set("include_initial", true);
+		let resync_query = start_query(&changes, &current.default_db(), BTreeMap::new());
+		
+		let results = try!(open_cursor(ConnRef::Shared(current), &query, Formats::default()));
+		
+		Ok(ResumableFeed {
+			conn: conn.clone(),
+			resync_query: resync_query,
+			results: Some(results),
+			decode: decode,
+			closed: false,
+		})
+	}
+	
+	fn resubscribe(&mut self) -> Result<FeedEvent<T>, QueryError> {
+		let current = try!(self.conn.get());
+		self.results = Some(try!(open_cursor(ConnRef::Shared(current), &self.resync_query, Formats::default())));
+		Ok(FeedEvent::Resynced)
+	}
+	
+	/// Stops the feed
+	pub fn close(&mut self) -> Result<(), QueryError> {
+		self.closed = true;
+		
+		match self.results.take() {
+			Some(mut results) => results.close(),
+			None => Ok(()),
+		}
+	}
+}
+
+impl<T> Iterator for ResumableFeed<T> {
+	type Item = Result<FeedEvent<T>, QueryError>;
+	
+	fn next(&mut self) -> Option<Result<FeedEvent<T>, QueryError>> {
+		if self.closed {
+			return None;
+		}
+		
+		let item = match self.results {
+			Some(ref mut results) => results.next(),
+			None => return Some(self.resubscribe()),
+		};
+		
+		match item {
+			Some(Ok(obj)) => Some((self.decode)(&obj).map(FeedEvent::Change).map_err(|e| QueryError::from(ConnectionError::Data(e)))),
+			Some(Err(QueryError::ConnectionError(ConnectionError::Io(_)))) |
+			Some(Err(QueryError::ConnectionError(ConnectionError::Timeout))) => {
+				//dropping the old cursor stops it if the connection survived, as after a timeout
+				self.results = None;
+				Some(self.resubscribe())
+			},
+			Some(Err(e)) => Some(Err(e)),
+			None => {
+				self.closed = true;
+				None
+			},
+		}
+	}
+}
@@ -0,0 +1,73 @@
+//! Conversion between the `rustc_serialize` JSON used on the wire and serde types
+
+use rustc_serialize::json;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value, Number};
+use err::DataError;
+
+pub fn json_to_value(datum: json::Json) -> Value {
+	match datum {
+		json::Json::I64(n) => Value::Number(Number::from(n)),
+		json::Json::U64(n) => Value::Number(Number::from(n)),
+		//NaN and infinity cannot be sent by the server, but are not representable either way
+		json::Json::F64(n) => Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null),
+		json::Json::String(s) => Value::String(s),
+		json::Json::Boolean(b) => Value::Bool(b),
+		json::Json::Array(arr) => Value::Array(arr.into_iter().map(json_to_value).collect()),
+		json::Json::Object(obj) => Value::Object(obj.into_iter().map(|(k, v)| (k, json_to_value(v))).collect()),
+		json::Json::Null => Value::Null,
+	}
+}
+
+pub fn value_to_json(value: Value) -> json::Json {
+	match value {
+		Value::Number(n) => {
+			if let Some(u) = n.as_u64() {
+				json::Json::U64(u)
+			} else if let Some(i) = n.as_i64() {
+				json::Json::I64(i)
+			} else {
+				json::Json::F64(n.as_f64().unwrap_or(0.0))
+			}
+		},
+		Value::String(s) => json::Json::String(s),
+		Value::Bool(b) => json::Json::Boolean(b),
+		Value::Array(arr) => json::Json::Array(arr.into_iter().map(value_to_json).collect()),
+		Value::Object(obj) => json::Json::Object(obj.into_iter().map(|(k, v)| (k, value_to_json(v))).collect()),
+		Value::Null => json::Json::Null,
+	}
+}
+
+/// Deserializes a datum received from the server
+pub fn from_json<T: DeserializeOwned>(datum: json::Json) -> Result<T, DataError> {
+	serde_json::from_value(json_to_value(datum)).map_err(|e| DataError::Decode(e.to_string()))
+}
+
+/// Serializes a value to be embedded in a query
+pub fn to_json<T: Serialize + ?Sized>(val: &T) -> Result<json::Json, serde_json::Error> {
+	serde_json::to_value(val).map(value_to_json)
+}
+
+#[cfg(test)]
+mod tests {
+	use rustc_serialize::json::Json;
+	use std::collections::BTreeMap;
+	use super::*;
+	
+	#[test]
+	fn test_round_trip() {
+		let datum = Json::from_str("{\"id\": 1, \"score\": -2.5, \"tags\": [\"a\", null], \"done\": false}").unwrap();
+		assert_eq!(value_to_json(json_to_value(datum.clone())), datum);
+		
+		let tags: BTreeMap<String, Vec<u64>> = from_json(Json::from_str("{\"a\": [1, 2]}").unwrap()).unwrap();
+		assert_eq!(tags["a"], vec![1, 2]);
+		
+		match from_json::<Vec<u64>>(Json::from_str("{\"a\": 1}").unwrap()) {
+			Err(DataError::Decode(_)) => (),
+			_ => panic!("expected a decode error"),
+		}
+		
+		assert_eq!(to_json(&vec!["a", "b"]).unwrap(), Json::from_str("[\"a\", \"b\"]").unwrap());
+	}
+}
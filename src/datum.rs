@@ -0,0 +1,442 @@
+use rustc_serialize::json::{self, ToJson};
+use rustc_serialize::base64::{self, ToBase64, FromBase64};
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+use std::slice;
+use std::vec;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use err::DataError;
+
+const REQL_TYPE: &'static str = "$reql_type$";
+
+/// Whether a pseudo-type is converted to its Rust representation or left as a raw object
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Format {
+	Native,
+	Raw,
+}
+
+impl Default for Format {
+	fn default() -> Format {
+		Format::Native
+	}
+}
+
+impl ToJson for Format {
+	fn to_json(&self) -> json::Json {
+		match *self {
+			Format::Native => "native",
+			Format::Raw => "raw",
+		}.to_json()
+	}
+}
+
+/// How pseudo-types in results are decoded, sent as the `time_format`, `binary_format` and
+/// `group_format` global optargs
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Default)]
+pub struct Formats {
+	pub time: Format,
+	pub binary: Format,
+	pub group: Format,
+	/// The server always sends GEOMETRY as an object, so this only decides whether `Datum`
+	/// converts it
+	pub geometry: Format,
+}
+
+//Time
+
+/// A point in time together with the UTC offset it was recorded in
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Time {
+	/// Seconds since the Unix epoch; the server keeps millisecond precision
+	pub epoch_time: f64,
+	/// Offset from UTC in seconds
+	pub offset: i32,
+}
+
+impl Time {
+	pub fn new(epoch_time: f64, offset: i32) -> Time {
+		Time {epoch_time: epoch_time, offset: offset}
+	}
+	
+	/// Converts a system time, recording it in UTC
+	pub fn from_system_time(time: SystemTime) -> Time {
+		let epoch_time = match time.duration_since(UNIX_EPOCH) {
+			Ok(since) => duration_secs(since),
+			Err(e) => -duration_secs(e.duration()),
+		};
+		Time::new(epoch_time, 0)
+	}
+	
+	pub fn to_system_time(&self) -> SystemTime {
+		let since = Duration::new(self.epoch_time.abs().trunc() as u64, (self.epoch_time.abs().fract() * 1e9) as u32);
+		
+		if self.epoch_time >= 0.0 {
+			UNIX_EPOCH + since
+		} else {
+			UNIX_EPOCH - since
+		}
+	}
+	
+	/// The offset formatted as `+HH:MM`
+	fn timezone(&self) -> String {
+		let sign = if self.offset < 0 { '-' } else { '+' };
+		let minutes = self.offset.abs() / 60;
+		format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+	}
+	
+	fn parse_timezone(timezone: &str) -> Option<i32> {
+		if timezone == "Z" {
+			return Some(0);
+		}
+		
+		let (sign, rest) = match timezone.chars().next() {
+			Some('+') => (1, &timezone[1..]),
+			Some('-') => (-1, &timezone[1..]),
+			_ => return None,
+		};
+		
+		let mut parts = rest.splitn(2, ':');
+		let hours = match parts.next().and_then(|h| h.parse::<i32>().ok()) {
+			Some(h) => h,
+			None => return None,
+		};
+		let minutes = match parts.next() {
+			Some(m) => match m.parse::<i32>() {
+				Ok(m) => m,
+				Err(_) => return None,
+			},
+			None => 0,
+		};
+		
+		Some(sign * (hours * 3600 + minutes * 60))
+	}
+}
+
+fn duration_secs(d: Duration) -> f64 {
+	d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+//Geometry
+
+/// A position given as longitude and latitude in degrees
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Point {
+	pub longitude: f64,
+	pub latitude: f64,
+}
+
+impl Point {
+	pub fn new(longitude: f64, latitude: f64) -> Point {
+		Point {longitude: longitude, latitude: latitude}
+	}
+	
+	fn from_json(coords: &json::Json) -> Option<Point> {
+		match coords.as_array() {
+			Some(pair) if pair.len() == 2 => match (pair[0].as_f64(), pair[1].as_f64()) {
+				(Some(lon), Some(lat)) => Some(Point::new(lon, lat)),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+}
+
+impl ToJson for Point {
+	fn to_json(&self) -> json::Json {
+		json::Json::Array(vec![self.longitude.to_json(), self.latitude.to_json()])
+	}
+}
+
+/// A GeoJSON geometry, as stored by the server
+#[derive(Debug,Clone,PartialEq)]
+pub enum Geometry {
+	Point(Point),
+	LineString(Vec<Point>),
+	/// The outer ring followed by any holes, each closed by repeating its first point
+	Polygon(Vec<Vec<Point>>),
+}
+
+impl Geometry {
+	fn from_json(obj: &json::Json) -> Option<Geometry> {
+		let coords = match obj.find("coordinates") {
+			Some(coords) => coords,
+			None => return None,
+		};
+		
+		let points = |line: &json::Json| line.as_array().and_then(|points| {
+			points.iter().map(Point::from_json).collect::<Option<Vec<_>>>()
+		});
+		
+		match obj.find("type").and_then(|t| t.as_string()) {
+			Some("Point") => Point::from_json(coords).map(Geometry::Point),
+			Some("LineString") => points(coords).map(Geometry::LineString),
+			Some("Polygon") => coords.as_array()
+				.and_then(|rings| rings.iter().map(&points).collect::<Option<Vec<_>>>())
+				.map(Geometry::Polygon),
+			_ => None,
+		}
+	}
+}
+
+impl ToJson for Geometry {
+	fn to_json(&self) -> json::Json {
+		let (geo_type, coords) = match *self {
+			Geometry::Point(ref point) => ("Point", point.to_json()),
+			Geometry::LineString(ref points) => ("LineString", points.to_json()),
+			Geometry::Polygon(ref rings) => ("Polygon", rings.to_json()),
+		};
+		
+		let mut obj = BTreeMap::new();
+		obj.insert(REQL_TYPE.to_owned(), "GEOMETRY".to_json());
+		obj.insert("type".to_owned(), geo_type.to_json());
+		obj.insert("coordinates".to_owned(), coords);
+		json::Json::Object(obj)
+	}
+}
+
+//Datum
+
+/// A ReQL value with its pseudo-types decoded
+#[derive(Debug,Clone,PartialEq)]
+pub enum Datum {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Datum>),
+	Object(BTreeMap<String, Datum>),
+	Time(Time),
+	Binary(Vec<u8>),
+	Geometry(Geometry),
+	/// The result of `group` followed by a reduction
+	Grouped(GroupedData),
+}
+
+impl Datum {
+	/// Decodes a result, converting pseudo-types whose format is `Format::Native`
+	pub fn from_json(datum: json::Json, formats: &Formats) -> Result<Datum, DataError> {
+		match datum {
+			json::Json::Null => Ok(Datum::Null),
+			json::Json::Boolean(b) => Ok(Datum::Bool(b)),
+			json::Json::I64(n) => Ok(Datum::Number(n as f64)),
+			json::Json::U64(n) => Ok(Datum::Number(n as f64)),
+			json::Json::F64(n) => Ok(Datum::Number(n)),
+			json::Json::String(s) => Ok(Datum::String(s)),
+			json::Json::Array(arr) => arr.into_iter()
+				.map(|d| Datum::from_json(d, formats))
+				.collect::<Result<Vec<_>, _>>()
+				.map(Datum::Array),
+			json::Json::Object(obj) => Datum::from_object(obj, formats),
+		}
+	}
+	
+	fn from_object(obj: BTreeMap<String, json::Json>, formats: &Formats) -> Result<Datum, DataError> {
+		let reql_type = obj.get(REQL_TYPE).and_then(|t| t.as_string()).map(|t| t.to_owned());
+		let malformed = |obj: BTreeMap<String, json::Json>| DataError::MalformedPacket(json::Json::Object(obj));
+		
+		match reql_type.as_ref().map(|t| t.as_str()) {
+			Some("TIME") if formats.time == Format::Native => {
+				let epoch_time = obj.get("epoch_time").and_then(|t| t.as_f64());
+				let offset = obj.get("timezone").and_then(|tz| tz.as_string()).and_then(Time::parse_timezone);
+				
+				match (epoch_time, offset) {
+					(Some(epoch_time), Some(offset)) => Ok(Datum::Time(Time::new(epoch_time, offset))),
+					_ => Err(malformed(obj)),
+				}
+			},
+			Some("BINARY") if formats.binary == Format::Native => {
+				match obj.get("data").and_then(|d| d.as_string()).and_then(|d| d.from_base64().ok()) {
+					Some(data) => Ok(Datum::Binary(data)),
+					None => Err(malformed(obj)),
+				}
+			},
+			Some("GEOMETRY") if formats.geometry == Format::Native => {
+				match Geometry::from_json(&json::Json::Object(obj.clone())) {
+					Some(geometry) => Ok(Datum::Geometry(geometry)),
+					None => Err(malformed(obj)),
+				}
+			},
+			Some("GROUPED_DATA") if formats.group == Format::Native => {
+				let pairs = match obj.get("data").and_then(|d| d.as_array()) {
+					Some(pairs) => pairs.clone(),
+					None => return Err(malformed(obj)),
+				};
+				
+				pairs.into_iter().map(|pair| match pair {
+					json::Json::Array(mut pair) if pair.len() == 2 => {
+						let reduction = try!(Datum::from_json(pair.pop().unwrap(), formats));
+						let group = try!(Datum::from_json(pair.pop().unwrap(), formats));
+						Ok((group, reduction))
+					},
+					_ => Err(malformed(obj.clone())),
+				}).collect::<Result<GroupedData, _>>().map(Datum::Grouped)
+			},
+			_ => obj.into_iter()
+				.map(|(k, v)| Datum::from_json(v, formats).map(|d| (k, d)))
+				.collect::<Result<BTreeMap<_, _>, _>>()
+				.map(Datum::Object),
+		}
+	}
+}
+
+impl ToJson for Datum {
+	fn to_json(&self) -> json::Json {
+		match *self {
+			Datum::Null => json::Json::Null,
+			Datum::Bool(b) => json::Json::Boolean(b),
+			Datum::Number(n) => n.to_json(),
+			Datum::String(ref s) => s.to_json(),
+			Datum::Array(ref arr) => arr.to_json(),
+			Datum::Object(ref obj) => obj.to_json(),
+			Datum::Time(ref time) => {
+				let mut obj = BTreeMap::new();
+				obj.insert(REQL_TYPE.to_owned(), "TIME".to_json());
+				obj.insert("epoch_time".to_owned(), time.epoch_time.to_json());
+				obj.insert("timezone".to_owned(), time.timezone().to_json());
+				json::Json::Object(obj)
+			},
+			Datum::Binary(ref data) => {
+				let mut obj = BTreeMap::new();
+				obj.insert(REQL_TYPE.to_owned(), "BINARY".to_json());
+				obj.insert("data".to_owned(), data.to_base64(base64::STANDARD).to_json());
+				json::Json::Object(obj)
+			},
+			Datum::Geometry(ref geometry) => geometry.to_json(),
+			Datum::Grouped(ref groups) => {
+				let data = groups.iter()
+					.map(|&(ref group, ref reduction)| json::Json::Array(vec![group.to_json(), reduction.to_json()]))
+					.collect();
+				
+				let mut obj = BTreeMap::new();
+				obj.insert(REQL_TYPE.to_owned(), "GROUPED_DATA".to_json());
+				obj.insert("data".to_owned(), json::Json::Array(data));
+				json::Json::Object(obj)
+			},
+		}
+	}
+}
+
+//GroupedData
+
+/// A map from each group to its reduction, in the order the server sent the groups.
+///
+/// Groups are keyed by `Datum`, which holds floating point numbers and so is neither `Ord` nor
+/// `Hash`; lookups compare each key in turn. `Queryable::run_grouped` decodes into a
+/// `BTreeMap` instead when the group type allows it.
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct GroupedData {
+	groups: Vec<(Datum, Datum)>,
+}
+
+impl GroupedData {
+	pub fn new() -> GroupedData {
+		GroupedData {groups: Vec::new()}
+	}
+	
+	/// The reduction for `group`, if there is one
+	pub fn get(&self, group: &Datum) -> Option<&Datum> {
+		self.groups.iter().find(|&&(ref g, _)| g == group).map(|&(_, ref reduction)| reduction)
+	}
+	
+	/// Sets the reduction for `group`, returning the one it replaces
+	pub fn insert(&mut self, group: Datum, reduction: Datum) -> Option<Datum> {
+		match self.groups.iter().position(|&(ref g, _)| *g == group) {
+			Some(i) => Some(::std::mem::replace(&mut self.groups[i].1, reduction)),
+			None => {
+				self.groups.push((group, reduction));
+				None
+			},
+		}
+	}
+	
+	pub fn len(&self) -> usize {
+		self.groups.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.groups.is_empty()
+	}
+	
+	/// The `(group, reduction)` pairs
+	pub fn iter(&self) -> slice::Iter<(Datum, Datum)> {
+		self.groups.iter()
+	}
+}
+
+impl FromIterator<(Datum, Datum)> for GroupedData {
+	fn from_iter<I: IntoIterator<Item=(Datum, Datum)>>(iter: I) -> GroupedData {
+		let mut grouped = GroupedData::new();
+		for (group, reduction) in iter {
+			grouped.insert(group, reduction);
+		}
+		grouped
+	}
+}
+
+impl IntoIterator for GroupedData {
+	type Item = (Datum, Datum);
+	type IntoIter = vec::IntoIter<(Datum, Datum)>;
+	
+	fn into_iter(self) -> vec::IntoIter<(Datum, Datum)> {
+		self.groups.into_iter()
+	}
+}
+
+impl<'a> IntoIterator for &'a GroupedData {
+	type Item = &'a (Datum, Datum);
+	type IntoIter = slice::Iter<'a, (Datum, Datum)>;
+	
+	fn into_iter(self) -> slice::Iter<'a, (Datum, Datum)> {
+		self.groups.iter()
+	}
+}
+
+impl ToJson for Time {
+	fn to_json(&self) -> json::Json {
+		Datum::Time(*self).to_json()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rustc_serialize::json::{Json, ToJson};
+	use super::*;
+	
+	#[test]
+	fn test_pseudo_types() {
+		let formats = Formats::default();
+		
+		let time = Json::from_str("{\"$reql_type$\": \"TIME\", \"epoch_time\": 1500000000.5, \"timezone\": \"-07:30\"}").unwrap();
+		assert_eq!(Datum::from_json(time.clone(), &formats).unwrap(), Datum::Time(Time::new(1500000000.5, -27000)));
+		assert_eq!(Datum::Time(Time::new(1500000000.5, -27000)).to_json(), time);
+		
+		let binary = Json::from_str("{\"$reql_type$\": \"BINARY\", \"data\": \"aGVsbG8=\"}").unwrap();
+		assert_eq!(Datum::from_json(binary.clone(), &formats).unwrap(), Datum::Binary(b"hello".to_vec()));
+		assert_eq!(Datum::Binary(b"hello".to_vec()).to_json(), binary);
+		
+		let point = Json::from_str("{\"$reql_type$\": \"GEOMETRY\", \"type\": \"Point\", \"coordinates\": [-122.4, 37.8]}").unwrap();
+		assert_eq!(Datum::from_json(point.clone(), &formats).unwrap(), Datum::Geometry(Geometry::Point(Point::new(-122.4, 37.8))));
+		
+		let grouped = Json::from_str("{\"$reql_type$\": \"GROUPED_DATA\", \"data\": [[\"a\", 2], [[1, 2], 1]]}").unwrap();
+		let groups = match Datum::from_json(grouped.clone(), &formats).unwrap() {
+			Datum::Grouped(groups) => groups,
+			d => panic!("expected grouped data, got {:?}", d),
+		};
+		assert_eq!(groups.len(), 2);
+		assert_eq!(groups.get(&Datum::String(String::from("a"))), Some(&Datum::Number(2.0)));
+		assert_eq!(groups.get(&Datum::Array(vec![Datum::Number(1.0), Datum::Number(2.0)])), Some(&Datum::Number(1.0)));
+		assert_eq!(groups.get(&Datum::String(String::from("b"))), None);
+		
+		//raw formats leave the object as it is
+		let raw = Formats {group: Format::Raw, geometry: Format::Raw, ..Formats::default()};
+		match Datum::from_json(grouped, &raw).unwrap() {
+			Datum::Object(obj) => assert_eq!(obj["$reql_type$"], Datum::String(String::from("GROUPED_DATA"))),
+			d => panic!("expected an object, got {:?}", d),
+		}
+		match Datum::from_json(point, &raw).unwrap() {
+			Datum::Object(obj) => assert_eq!(obj["$reql_type$"], Datum::String(String::from("GEOMETRY"))),
+			d => panic!("expected an object, got {:?}", d),
+		}
+	}
+}
@@ -0,0 +1,157 @@
+use rustc_serialize::json::{self, ToJson};
+use std::collections::BTreeMap;
+use ql2::TermType;
+use super::{TreeNode, Value, Queryable, Stream, ReQLGenState, make_term};
+use math::Unary;
+
+//Selector
+
+/// A field to keep, drop or test for in `pluck`, `without`, `has_fields` and `with_fields`.
+/// A `&str` converts to a top-level field, and `r::nested` selects fields inside an object.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Selector {
+	Field(String),
+	/// Fields of the object stored in the named field
+	Nested(String, Vec<Selector>),
+}
+
+impl<'a> From<&'a str> for Selector {
+	fn from(name: &'a str) -> Selector {
+		Selector::Field(name.to_owned())
+	}
+}
+
+impl From<String> for Selector {
+	fn from(name: String) -> Selector {
+		Selector::Field(name)
+	}
+}
+
+impl TreeNode for Selector {
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		match *self {
+			Selector::Field(ref name) => name.to_json(),
+			Selector::Nested(ref name, ref fields) => {
+				let fields = fields.iter().map(|f| f.get_reql_json(state)).collect();
+				let mut obj = BTreeMap::new();
+				obj.insert(name.clone(), make_term(TermType::MAKE_ARRAY, fields, None));
+				json::Json::Object(obj)
+			},
+		}
+	}
+}
+
+/// Selects `fields` inside the object stored in the field `name`,
+/// e.g. `r::nested("address", vec!["city", "country"])`
+pub fn nested<I>(name: &str, fields: I) -> Selector
+	where I: IntoIterator, I::Item: Into<Selector>
+{
+	Selector::Nested(name.to_owned(), fields.into_iter().map(Into::into).collect())
+}
+
+//Fields
+
+/// A term taking a list of field selectors: the result of `pluck`, `without`, `has_fields` or
+/// `with_fields`
+pub struct Fields<S>
+	where S: TreeNode
+{
+	term_type: TermType,
+	source: S,
+	fields: Vec<Selector>,
+}
+
+impl<S> Fields<S>
+	where S: TreeNode
+{
+	pub fn new<I>(term_type: TermType, source: S, fields: I) -> Fields<S>
+		where I: IntoIterator, I::Item: Into<Selector>
+	{
+		Fields {
+			term_type: term_type,
+			source: source,
+			fields: fields.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+impl<S> TreeNode for Fields<S>
+	where S: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let mut args = vec![self.source.get_reql_json(state)];
+		args.extend(self.fields.iter().map(|f| f.get_reql_json(state)));
+		make_term(self.term_type, args, None)
+	}
+}
+
+impl<S> Value for Fields<S> where S: Value {}
+
+impl<S> Stream for Fields<S> where S: Stream {}
+
+impl<S> Queryable for Fields<S> where S: TreeNode {}
+
+value_ops!([S: Value,] Fields<S>);
+
+//Merge
+
+/// Objects merged into a document, with fields from `other` taking precedence
+pub struct Merge<S, T>
+	where S: TreeNode, T: TreeNode
+{
+	source: S,
+	other: T,
+}
+
+impl<S, T> Merge<S, T>
+	where S: TreeNode, T: TreeNode
+{
+	pub fn new(source: S, other: T) -> Merge<S, T> {
+		Merge {source: source, other: other}
+	}
+}
+
+impl<S, T> TreeNode for Merge<S, T>
+	where S: TreeNode, T: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let other = self.other.get_reql_json(state);
+		make_term(TermType::MERGE, vec![source, other], None)
+	}
+}
+
+impl<S, T> Value for Merge<S, T> where S: Value, T: TreeNode {}
+
+impl<S, T> Stream for Merge<S, T> where S: Stream, T: TreeNode {}
+
+impl<S, T> Queryable for Merge<S, T> where S: TreeNode, T: TreeNode {}
+
+value_ops!([S: Value, T: TreeNode,] Merge<S, T>);
+
+//Literal
+
+/// Marks an object in `merge` or `update` to replace the existing value of its field rather
+/// than being merged into it
+pub fn literal<T: Value>(val: T) -> Unary<T> {
+	Unary::new(TermType::LITERAL, val)
+}
+
+/// A `literal` with no value, created by `r::literal_remove`
+#[derive(Copy,Clone)]
+pub struct LiteralRemove;
+
+impl TreeNode for LiteralRemove {
+	fn get_reql_json(&self, _state: &mut ReQLGenState) -> json::Json {
+		make_term(TermType::LITERAL, vec![], None)
+	}
+}
+
+impl Value for LiteralRemove {}
+
+value_ops!(LiteralRemove);
+
+/// Marks a field in `merge` or `update` to be removed from the document
+pub fn literal_remove() -> LiteralRemove {
+	LiteralRemove
+}
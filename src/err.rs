@@ -1,15 +1,39 @@
 use rustc_serialize::json;
 use std::fmt;
 use std::error::{self, Error};
+use openssl::error::ErrorStack;
+use openssl::ssl::Error as SslError;
+
+pub use ql2::ErrorType;
 
 //***Declarations***
 
 #[derive(Debug)]
 pub enum QueryError {
 	ConnectionError(ConnectionError),
-	ClientError(json::Json),
-	CompileError(json::Json),
-	RuntimeError(json::Json),
+	ClientError(ReqlError),
+	CompileError(ReqlError),
+	RuntimeError(ReqlError),
+}
+
+/// The details of an error response sent by the server
+#[derive(Debug,Clone)]
+pub struct ReqlError {
+	/// The error message reported by the server
+	pub message: String,
+	/// The path through the query term tree to the term that caused the error
+	pub backtrace: Vec<Frame>,
+	/// The class of the error, only reported for runtime errors
+	pub error_type: Option<ErrorType>,
+}
+
+/// A single step in a server backtrace
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Frame {
+	/// The positional argument at the given index
+	Pos(u64),
+	/// The optional argument with the given name
+	Opt(String),
 }
 
 #[derive(Debug,Clone)]
@@ -18,6 +42,10 @@ pub enum DataError {
 	InvalidJson(String),
 	NoDataReceived,
 	MalformedPacket(json::Json),
+	/// A result could not be deserialized into the requested type
+	Decode(String),
+	/// A value could not be serialized to be embedded in a query
+	Encode(String),
 }
 
 #[derive(Debug,Clone)]
@@ -32,19 +60,63 @@ pub enum ConnectionError {
 	Io(::std::io::Error),
 	Data(DataError),
 	Auth(AuthError),
+	Tls(SslError),
+	Timeout,
+}
+
+//***Decoding***
+
+impl ReqlError {
+	/// Extracts the message, backtrace and error type from an error response
+	pub fn from_response(reply: &json::Json) -> Result<ReqlError, DataError> {
+		let message = match reply.find("r") {
+			Some(&json::Json::Array(ref r)) => match r.first() {
+				Some(&json::Json::String(ref s)) => s.clone(),
+				_ => return Err(DataError::MalformedPacket(reply.clone())),
+			},
+			_ => return Err(DataError::MalformedPacket(reply.clone())),
+		};
+		
+		let backtrace = match reply.find("b") {
+			Some(&json::Json::Array(ref frames)) => try!(frames.iter().map(|frame| {
+				match *frame {
+					json::Json::U64(n) => Ok(Frame::Pos(n)),
+					json::Json::String(ref s) => Ok(Frame::Opt(s.clone())),
+					_ => Err(DataError::MalformedPacket(reply.clone())),
+				}
+			}).collect::<Result<Vec<_>, _>>()),
+			_ => Vec::new(),
+		};
+		
+		let error_type = reply.find("e").and_then(|e| e.as_u64()).and_then(ErrorType::from_u64);
+		
+		Ok(ReqlError {
+			message: message,
+			backtrace: backtrace,
+			error_type: error_type,
+		})
+	}
 }
 
 //***Display implementations***
 
 impl fmt::Display for QueryError {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+		match *self {
+			QueryError::ConnectionError(ref ce) => write!(f, "{}", ce),
+			QueryError::ClientError(ref e) |
+			QueryError::CompileError(ref e) |
+			QueryError::RuntimeError(ref e) => write!(f, "{}: {}", self.description(), e.message),
+		}
     }
 }
 
 impl fmt::Display for DataError {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+		match *self {
+			DataError::Decode(ref msg) | DataError::Encode(ref msg) => write!(f, "{}: {}", self.description(), msg),
+			_ => write!(f, "{}", self.description()),
+		}
     }
 }
 
@@ -80,6 +152,8 @@ impl error::Error for DataError {
 			DataError::InvalidJson(ref _s) => "The received packet could not be parsed as JSON",
 			DataError::NoDataReceived => "No data was received",
 			DataError::MalformedPacket(ref _json) => "A malformed packet was received",
+			DataError::Decode(ref _msg) => "A result could not be decoded into the requested type",
+			DataError::Encode(ref _msg) => "A value could not be encoded to be embedded in a query",
 		}
 	}
 }
@@ -100,6 +174,8 @@ impl error::Error for ConnectionError {
 			ConnectionError::Io(ref e) => e.description(),
 			ConnectionError::Data(ref e) => e.description(),
 			ConnectionError::Auth(ref e) => e.description(),
+			ConnectionError::Tls(ref e) => e.description(),
+			ConnectionError::Timeout => "The operation did not complete within the configured timeout",
 		}
 	}
 }
@@ -112,6 +188,12 @@ impl From<::std::io::Error> for ConnectionError {
 	}
 }
 
+impl From<ErrorStack> for ConnectionError {
+	fn from(err: ErrorStack) -> ConnectionError {
+		ConnectionError::Tls(SslError::Ssl(err))
+	}
+}
+
 impl From<DataError> for ConnectionError {
 	fn from(err: DataError) -> ConnectionError {
 		ConnectionError::Data(err)
@@ -126,7 +208,7 @@ impl From<AuthError> for ConnectionError {
 
 impl From<::std::io::Error> for QueryError {
 	fn from(err: ::std::io::Error) -> QueryError {
-		Self::from(ConnectionError::Io(err))
+		Self::from(ConnectionError::from(err))
 	}
 }
 
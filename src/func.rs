@@ -0,0 +1,118 @@
+use rustc_serialize::json::{self, ToJson};
+use std::marker::PhantomData;
+use ql2::TermType;
+use super::{TreeNode, ReQLGenState, ClosureVar, make_term};
+
+/// A Rust closure that can be the body of a ReQL function. It is implemented for closures taking
+/// one to five `ClosureVar`s and returning a term; `Args` is the tuple of parameter types, which
+/// keeps the implementations for each arity apart.
+pub trait Closure<Args> {
+	/// Allocates a fresh variable for each parameter and calls the closure with them, returning
+	/// the variables and the serialized body
+	fn call(&self, state: &mut ReQLGenState) -> (Vec<ClosureVar>, json::Json);
+}
+
+macro_rules! impl_closure {
+	(@var $var:ident) => { ClosureVar };
+	($($var:ident),+) => {
+		impl<F, T> Closure<($(impl_closure!(@var $var),)+)> for F
+			where
+				F: Fn($(impl_closure!(@var $var)),+) -> T,
+				T: TreeNode
+		{
+			fn call(&self, state: &mut ReQLGenState) -> (Vec<ClosureVar>, json::Json) {
+				$(let $var = state.gen_closure_var();)+
+				let body = (self)($($var),+).get_reql_json(state);
+				(vec![$($var),+], body)
+			}
+		}
+	};
+}
+
+impl_closure!(a);
+impl_closure!(a, b);
+impl_closure!(a, b, c);
+impl_closure!(a, b, c, d);
+impl_closure!(a, b, c, d, e);
+
+/// A ReQL function built from a Rust closure. `Func<P>` takes one parameter, and `Func2` to
+/// `Func5` take more; `r::func` picks the arity from the closure.
+///
+/// Every parameter is given a fresh variable from the `ReQLGenState` when the query is
+/// serialized, so a closure nested inside another never shadows the outer closure's variables.
+pub struct Func<P, A = (ClosureVar,)> {
+	f: P,
+	args: PhantomData<A>,
+}
+
+/// A two-parameter ReQL function, such as the combining function of `reduce`
+pub type Func2<P> = Func<P, (ClosureVar, ClosureVar)>;
+
+/// A three-parameter ReQL function, such as the `emit` function of `fold` or an insert
+/// conflict handler
+pub type Func3<P> = Func<P, (ClosureVar, ClosureVar, ClosureVar)>;
+
+/// A four-parameter ReQL function
+pub type Func4<P> = Func<P, (ClosureVar, ClosureVar, ClosureVar, ClosureVar)>;
+
+/// A five-parameter ReQL function
+pub type Func5<P> = Func<P, (ClosureVar, ClosureVar, ClosureVar, ClosureVar, ClosureVar)>;
+
+impl<P, A> Func<P, A>
+	where P: Closure<A>
+{
+	pub fn new(f: P) -> Func<P, A> {
+		Func {f: f, args: PhantomData}
+	}
+}
+
+impl<P, A> TreeNode for Func<P, A>
+	where P: Closure<A>
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let (vars, body) = self.f.call(state);
+		
+		make_term(TermType::FUNC, vec![
+			make_term(TermType::MAKE_ARRAY, vars.iter().map(|v| v.n.to_json()).collect(), None),
+			body
+		], None)
+	}
+}
+
+/// Builds a ReQL function from a closure taking one to five `ClosureVar`s, for use where a
+/// function is passed as a value, e.g. `r::func(|a: ClosureVar, b: ClosureVar| a.add(b))`.
+/// The parameter types must be written out so that the arity can be inferred.
+pub fn func<P, A>(f: P) -> Func<P, A>
+	where P: Closure<A>
+{
+	Func::new(f)
+}
+
+#[cfg(test)]
+mod tests {
+	use rustc_serialize::json;
+	use super::{func, Func, Func2, Func4};
+	use {TreeNode, Value, ReQLGenState, ClosureVar};
+	
+	#[test]
+	fn test_func_arity() {
+		let f = Func4::new(|a: ClosureVar, _: ClosureVar, _: ClosureVar, d: ClosureVar| a.add(d));
+		let expected = json::Json::from_str("[69,[[2,[0,1,2,3]],[24,[[10,[0]],[10,[3]]]]]]").unwrap();
+		assert_eq!(f.get_reql_json(&mut ReQLGenState::new()), expected);
+		
+		//the arity is inferred from the closure
+		let f = func(|a: ClosureVar, b: ClosureVar, c: ClosureVar, _: ClosureVar, e: ClosureVar| a.add(b).add(c).add(e));
+		let expected = json::Json::from_str("[69,[[2,[0,1,2,3,4]],[24,[[24,[[24,[[10,[0]],[10,[1]]]],[10,[2]]]],[10,[4]]]]]]").unwrap();
+		assert_eq!(f.get_reql_json(&mut ReQLGenState::new()), expected);
+	}
+	
+	#[test]
+	fn test_nested_scoping() {
+		//the inner closure gets a new variable, and can still refer to the outer ones
+		let func = Func2::new(|acc: ClosureVar, x: ClosureVar| {
+			Func::<_>::new(move |y: ClosureVar| acc.add(x).add(y))
+		});
+		let expected = json::Json::from_str("[69,[[2,[0,1]],[69,[[2,[2]],[24,[[24,[[10,[0]],[10,[1]]]],[10,[2]]]]]]]]").unwrap();
+		assert_eq!(func.get_reql_json(&mut ReQLGenState::new()), expected);
+	}
+}
@@ -0,0 +1,170 @@
+use rustc_serialize::json;
+use serde::{Deserialize, Deserializer};
+use serde::de::{DeserializeOwned, Error};
+use serde_json;
+use ql2::TermType;
+use super::{TreeNode, Queryable, Stream, ReQLGenState, ClosureVar, Table, OptArgs, Func2, make_term};
+
+//EqJoin
+
+/// Each element of a sequence paired with the document of a table whose index matches a key
+/// of the element. The key is a field of the element or the result of a function.
+pub struct EqJoin<'a, S, K>
+	where S: Stream, K: TreeNode
+{
+	source: S,
+	key: K,
+	table: Table<'a>,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S, K> EqJoin<'a, S, K>
+	where S: Stream, K: TreeNode
+{
+	pub fn new(source: S, key: K, table: Table<'a>) -> EqJoin<'a, S, K> {
+		EqJoin {source: source, key: key, table: table, optargs: OptArgs::new()}
+	}
+	
+	/// Match against a secondary index of the table instead of its primary key
+	pub fn index(mut self, name: &str) -> EqJoin<'a, S, K> {
+		self.optargs.set("index", name.to_owned());
+		self
+	}
+	
+	/// If true, the pairs are returned in the order of the left sequence
+	pub fn ordered(mut self, val: bool) -> EqJoin<'a, S, K> {
+		self.optargs.set("ordered", val);
+		self
+	}
+}
+
+impl<'a, S, K> TreeNode for EqJoin<'a, S, K>
+	where S: Stream, K: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let key = self.key.get_reql_json(state);
+		let table = self.table.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::EQ_JOIN, vec![source, key, table], optargs)
+	}
+}
+
+impl<'a, S, K> Stream for EqJoin<'a, S, K> where S: Stream, K: TreeNode {}
+
+impl<'a, S, K> Queryable for EqJoin<'a, S, K> where S: Stream, K: TreeNode {}
+
+//Join
+
+/// Each pair of elements of two sequences for which a predicate holds: the result of
+/// `inner_join` or `outer_join`
+pub struct Join<S, T, P>
+	where S: Stream, T: Stream
+{
+	term_type: TermType,
+	source: S,
+	other: T,
+	predicate: Func2<P>,
+}
+
+impl<S, T, P, U> Join<S, T, P>
+	where S: Stream, T: Stream, P: Fn(ClosureVar, ClosureVar) -> U, U: TreeNode
+{
+	pub fn new(term_type: TermType, source: S, other: T, predicate: P) -> Join<S, T, P> {
+		Join {term_type: term_type, source: source, other: other, predicate: Func2::new(predicate)}
+	}
+}
+
+impl<S, T, P, U> TreeNode for Join<S, T, P>
+	where S: Stream, T: Stream, P: Fn(ClosureVar, ClosureVar) -> U, U: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let other = self.other.get_reql_json(state);
+		let predicate = self.predicate.get_reql_json(state);
+		make_term(self.term_type, vec![source, other, predicate], None)
+	}
+}
+
+impl<S, T, P, U> Stream for Join<S, T, P> where S: Stream, T: Stream, P: Fn(ClosureVar, ClosureVar) -> U, U: TreeNode {}
+
+impl<S, T, P, U> Queryable for Join<S, T, P> where S: Stream, T: Stream, P: Fn(ClosureVar, ClosureVar) -> U, U: TreeNode {}
+
+//Zip
+
+/// The pairs of a join merged into single documents, with fields from the right taking precedence
+pub struct Zip<S>
+	where S: Stream
+{
+	source: S,
+}
+
+impl<S> Zip<S>
+	where S: Stream
+{
+	pub fn new(source: S) -> Zip<S> {
+		Zip {source: source}
+	}
+}
+
+impl<S> TreeNode for Zip<S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		make_term(TermType::ZIP, vec![source], None)
+	}
+}
+
+impl<S> Stream for Zip<S> where S: Stream {}
+
+impl<S> Queryable for Zip<S> where S: Stream {}
+
+//JoinPair
+
+/// An element of the result of a join, for decoding with `Queryable::run_as`.
+///
+/// `outer_join` has no right document for unmatched elements; a missing `right` decodes as
+/// null, so use `JoinPair<L, Option<R>>` for outer joins.
+#[derive(Debug,Clone,PartialEq)]
+pub struct JoinPair<L, R> {
+	pub left: L,
+	pub right: R,
+}
+
+impl<'de, L, R> Deserialize<'de> for JoinPair<L, R>
+	where L: DeserializeOwned, R: DeserializeOwned
+{
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<JoinPair<L, R>, D::Error> {
+		let mut obj = match try!(serde_json::Value::deserialize(deserializer)) {
+			serde_json::Value::Object(obj) => obj,
+			_ => return Err(D::Error::custom("expected a join result with left and right fields")),
+		};
+		
+		let left = try!(obj.remove("left").ok_or_else(|| D::Error::missing_field("left")));
+		let right = obj.remove("right").unwrap_or(serde_json::Value::Null);
+		
+		Ok(JoinPair {
+			left: try!(serde_json::from_value(left).map_err(D::Error::custom)),
+			right: try!(serde_json::from_value(right).map_err(D::Error::custom)),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::JoinPair;
+	
+	#[test]
+	fn test_decode_join_pair() {
+		let pair: JoinPair<i64, String> = serde_json::from_str("{\"left\":1,\"right\":\"a\"}").unwrap();
+		assert_eq!(pair, JoinPair {left: 1, right: "a".to_owned()});
+		
+		//outer joins leave out the right document when nothing matched
+		let pair: JoinPair<i64, Option<String>> = serde_json::from_str("{\"left\":2}").unwrap();
+		assert_eq!(pair, JoinPair {left: 2, right: None});
+		
+		assert!(serde_json::from_str::<JoinPair<i64, String>>("{\"right\":\"a\"}").is_err());
+	}
+}
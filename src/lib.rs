@@ -1,47 +1,63 @@
 extern crate rustc_serialize;
 extern crate openssl;
 extern crate byteorder;
+extern crate serde;
+extern crate serde_json;
 #[macro_use] extern crate maplit;
+#[cfg(feature = "async")] extern crate futures;
+#[cfg(feature = "async")] extern crate tokio;
 
 use rustc_serialize::json::{self, ToJson};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use std::ops::Fn;
 use std::marker::Sized;
 use std::result::Result;
-use err::QueryError;
+use std::collections::{VecDeque, BTreeMap};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
+use err::{QueryError, ConnectionError, DataError, ReqlError};
+use ql2::{TermType, QueryType, ResponseNote};
 
 pub mod prelude {
 	pub use super::{
 		TreeNode,
+		Stream,
 		Selection,
+		SingleSelection,
 		Value,
 		Queryable,
+		WriteQuery,
 	};
 }
+#[macro_use] pub mod math;
+pub mod document;
+pub mod transform;
+pub mod aggregate;
 pub mod net;
 pub mod err;
+pub mod ql2;
+pub mod write;
+pub mod select;
+pub mod datum;
+pub mod changes;
+pub mod func;
+pub mod join;
+mod convert;
 
 pub use net::*;
-
-#[allow(non_camel_case_types,dead_code)]
-enum TermTypes {
-	DATUM = 1,
-	MAKE_ARRAY = 2,
-	VAR = 10,
-	DB = 14,
-	TABLE = 15,
-	EQ = 17,
-	FILTER = 39,
-	FUNC = 69,
-}
-
-#[allow(non_camel_case_types,dead_code)]
-enum QueryTypes {
-	START = 1,
-	CONTINUE = 2,
-	STOP = 3,
-	NOREPLY_WAIT = 4,
-	SERVER_INFO = 5,
-}
+pub use write::*;
+pub use select::*;
+pub use datum::*;
+pub use changes::*;
+pub use math::*;
+pub use document::*;
+pub use transform::*;
+pub use aggregate::*;
+pub use func::*;
+pub use join::*;
+pub use ql2::ResponseType;
 
 pub struct ReQLGenState {
 	nvars: u64
@@ -58,47 +74,520 @@ impl ReQLGenState {
 		retval
 	}
 }
- 
+
 pub trait TreeNode {
 	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json;
 }
 
+/// Builds the `[type, [args...], {optargs...}]` representation of a term
+fn make_term(term_type: TermType, args: Vec<json::Json>, optargs: Option<json::Json>) -> json::Json {
+	let mut term = vec![
+		(term_type as u32).to_json(),
+		json::Json::Array(args),
+	];
+	
+	if let Some(optargs) = optargs {
+		term.push(optargs);
+	}
+	
+	json::Json::Array(term)
+}
+
+/// The optional arguments attached to a term
+struct OptArgs<'a> {
+	args: BTreeMap<&'static str, Box<TreeNode + 'a>>,
+}
+
+impl<'a> OptArgs<'a> {
+	fn new() -> OptArgs<'a> {
+		OptArgs {args: BTreeMap::new()}
+	}
+	
+	fn set<T: TreeNode + 'a>(&mut self, name: &'static str, val: T) {
+		self.args.insert(name, Box::new(val));
+	}
+	
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> Option<json::Json> {
+		if self.args.is_empty() {
+			None
+		} else {
+			Some(json::Json::Object(self.args.iter().map(|(k, v)| ((*k).to_owned(), v.get_reql_json(state))).collect()))
+		}
+	}
+}
+
 pub trait Value : TreeNode {
-	fn eq<'a, T>(&'a self, other: &'a T) -> Eq<'a, Self, T>
-		where
-			T: 'a+Value,
-			Self: Value+Sized,
+	/// True if the values are equal
+	fn eq<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::EQ, self, other)
+	}
+	
+	/// True if the values are not equal
+	fn ne<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::NE, self, other)
+	}
+	
+	/// True if this value is less than `other`
+	fn lt<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::LT, self, other)
+	}
+	
+	/// True if this value is less than or equal to `other`
+	fn le<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::LE, self, other)
+	}
+	
+	/// True if this value is greater than `other`
+	fn gt<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::GT, self, other)
+	}
+	
+	/// True if this value is greater than or equal to `other`
+	fn ge<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::GE, self, other)
+	}
+	
+	/// True if both values are true. Also available as `&` on term types.
+	fn and<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::AND, self, other)
+	}
+	
+	/// True if either value is true. Also available as `|` on term types.
+	fn or<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::OR, self, other)
+	}
+	
+	/// Adds numbers, concatenates strings and arrays, or offsets a time by seconds
+	fn add<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::ADD, self, other)
+	}
+	
+	/// Subtracts numbers, or gives the seconds between two times
+	fn sub<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::SUB, self, other)
+	}
+	
+	/// Multiplies numbers, or repeats an array
+	fn mul<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::MUL, self, other)
+	}
+	
+	/// Divides numbers
+	fn div<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::DIV, self, other)
+	}
+	
+	/// The remainder of dividing by `other`, ReQL's `mod`
+	fn rem<T>(self, other: T) -> Binary<Self, T>
+		where T: Value, Self: Sized
+	{
+		Binary::new(TermType::MOD, self, other)
+	}
+	
+	/// Logical negation. Also available as `!` on term types.
+	fn not(self) -> Unary<Self>
+		where Self: Sized
+	{
+		Unary::new(TermType::NOT, self)
+	}
+	
+	/// Rounds down to the nearest integer
+	fn floor(self) -> Unary<Self>
+		where Self: Sized
+	{
+		Unary::new(TermType::FLOOR, self)
+	}
+	
+	/// Rounds up to the nearest integer
+	fn ceil(self) -> Unary<Self>
+		where Self: Sized
+	{
+		Unary::new(TermType::CEIL, self)
+	}
+	
+	/// Rounds to the nearest integer, with halves rounded away from zero
+	fn round(self) -> Unary<Self>
+		where Self: Sized
+	{
+		Unary::new(TermType::ROUND, self)
+	}
+	
+	/// The value of a field of an object, or of that field of each object in an array
+	fn get_field(self, name: &str) -> Binary<Self, String>
+		where Self: Sized
+	{
+		Binary::new(TermType::GET_FIELD, self, name.to_owned())
+	}
+	
+	/// A field of an object if `key` is a string, or an element of an array if it is a number
+	fn bracket<K>(self, key: K) -> Binary<Self, K>
+		where K: Value, Self: Sized
+	{
+		Binary::new(TermType::BRACKET, self, key)
+	}
+	
+	/// Keeps only the selected fields of an object, or of each object in an array
+	fn pluck<I>(self, fields: I) -> Fields<Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Fields::new(TermType::PLUCK, self, fields)
+	}
+	
+	/// Removes the selected fields from an object, or from each object in an array
+	fn without<I>(self, fields: I) -> Fields<Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Fields::new(TermType::WITHOUT, self, fields)
+	}
+	
+	/// True if the object has all of the selected fields, and they are not null
+	fn has_fields<I>(self, fields: I) -> Fields<Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Fields::new(TermType::HAS_FIELDS, self, fields)
+	}
+	
+	/// Keeps the objects in an array that have all of the selected fields, plucking those fields
+	fn with_fields<I>(self, fields: I) -> Fields<Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Fields::new(TermType::WITH_FIELDS, self, fields)
+	}
+	
+	/// Merges `other` into the object, recursively. Use `r::literal` to replace a nested object instead.
+	fn merge<T>(self, other: T) -> Merge<Self, T>
+		where T: Value, Self: Sized
+	{
+		Merge::new(self, other)
+	}
+	
+	/// Merges the result of a function of the object into it
+	fn merge_fn<P, T>(self, func: P) -> Merge<Self, Func<P>>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Merge::new(self, Func::new(func))
+	}
+	
+	/// The names of the fields of an object, in sorted order
+	fn keys(self) -> Unary<Self>
+		where Self: Sized
+	{
+		Unary::new(TermType::KEYS, self)
+	}
+	
+	/// The values of the fields of an object, in the order of their names
+	fn values(self) -> Unary<Self>
+		where Self: Sized
 	{
-		Eq {a: self, b: other}
+		Unary::new(TermType::VALUES, self)
+	}
+	
+	/// Turns grouped data, such as the result of `group(...).count()`, into an array of objects
+	/// with `group` and `reduction` fields
+	fn ungroup(self) -> Ungroup<Self>
+		where Self: Sized
+	{
+		Ungroup::new(self)
+	}
+	
+	/// Treats a value holding an array, such as `r::array()`, `r::expr(&vec![...])` or a field of
+	/// a document, as a sequence so that the `Stream` methods can be applied to it
+	fn as_stream(self) -> AsStream<Self>
+		where Self: Sized
+	{
+		AsStream::new(self)
 	}
 }
 
-pub struct Eq<'a, T1, T2>
-	where T1: 'a+Value, T2: 'a+Value
-{
-	a: &'a T1,
-	b: &'a T2,
+/// A single decoded response envelope (`t`, `r`, `n`, `b` and `p` fields)
+pub struct Response {
+	response_type: ResponseType,
+	data: Vec<json::Json>,
+	notes: Vec<ResponseNote>,
+	profile: Option<json::Json>,
 }
 
-impl<'a, T1, T2> Value for Eq<'a, T1, T2>
-	where T1: Value, T2: Value
-{}
+impl Response {
+	/// Decodes a response envelope received from the server.
+	/// Error responses are returned as the matching `QueryError` variant.
+	pub fn from_json(reply: json::Json) -> Result<Response, QueryError> {
+		let response_type = match reply.find("t") {
+			Some(&json::Json::U64(t)) => ResponseType::from_u64(t),
+			_ => None,
+		};
+		
+		let response_type = try!(response_type.ok_or(ConnectionError::Data(DataError::MalformedPacket(reply.clone()))));
+		
+		match response_type {
+			ResponseType::CLIENT_ERROR => return Err(QueryError::ClientError(try!(ReqlError::from_response(&reply).map_err(ConnectionError::Data)))),
+			ResponseType::COMPILE_ERROR => return Err(QueryError::CompileError(try!(ReqlError::from_response(&reply).map_err(ConnectionError::Data)))),
+			ResponseType::RUNTIME_ERROR => return Err(QueryError::RuntimeError(try!(ReqlError::from_response(&reply).map_err(ConnectionError::Data)))),
+			_ => (),
+		}
+		
+		let notes = match reply.find("n") {
+			Some(&json::Json::Array(ref notes)) => try!(notes.iter()
+				.map(|n| n.as_u64())
+				.collect::<Option<Vec<_>>>()
+				.ok_or(ConnectionError::Data(DataError::MalformedPacket(reply.clone()))))
+				.into_iter()
+				.filter_map(ResponseNote::from_u64)
+				.collect(),
+			None => Vec::new(),
+			_ => return Err(QueryError::from(ConnectionError::Data(DataError::MalformedPacket(reply)))),
+		};
+		
+		let profile = reply.find("p").cloned();
+		
+		let data = match reply {
+			json::Json::Object(mut obj) => match obj.remove("r") {
+				Some(json::Json::Array(data)) => data,
+				None if response_type == ResponseType::WAIT_COMPLETE => Vec::new(),
+				_ => return Err(QueryError::from(ConnectionError::Data(DataError::MalformedPacket(json::Json::Object(obj))))),
+			},
+			_ => unreachable!(),
+		};
+		
+		Ok(Response {
+			response_type: response_type,
+			data: data,
+			notes: notes,
+			profile: profile,
+		})
+	}
+	
+	pub fn response_type(&self) -> ResponseType {
+		self.response_type
+	}
+	
+	/// True if the server has more results available for this query
+	pub fn is_partial(&self) -> bool {
+		self.response_type == ResponseType::SUCCESS_PARTIAL
+	}
+	
+	pub fn data(&self) -> &[json::Json] {
+		&self.data
+	}
+	
+	pub fn into_data(self) -> Vec<json::Json> {
+		self.data
+	}
+	
+	pub fn notes(&self) -> &[ResponseNote] {
+		&self.notes
+	}
+	
+	/// Profiling information, only present if the query was run with `profile: true`
+	pub fn profile(&self) -> Option<&json::Json> {
+		self.profile.as_ref()
+	}
+}
 
-impl<'a, T1, T2> TreeNode for Eq<'a, T1, T2>
-	where T1: Value, T2: Value
-{
-	fn get_reql_json(&self, mut state: &mut ReQLGenState) -> json::Json {
-		json::Json::Array(vec![
-			(TermTypes::EQ as u32).to_json(),
-			json::Json::Array(vec![
-				self.a.get_reql_json(&mut state),
-				self.b.get_reql_json(&mut state),
-			])
-		])
+/// A cursor over the results of a query.
+///
+/// Batches are fetched from the server on demand by sending CONTINUE on the query's token.
+/// If the cursor is dropped before the server has sent the final batch, a STOP is sent.
+/// An atom result is yielded as a single item.
+pub struct ResultSet<'a, S: 'a + Transport = Socket> {
+	conn: ConnRef<'a, S>,
+	token: u64,
+	response_type: ResponseType,
+	buffer: VecDeque<json::Json>,
+	notes: Vec<ResponseNote>,
+	profile: Option<json::Json>,
+	formats: Formats,
+}
+
+/// The connection a cursor reads from, either borrowed from the caller or shared with a
+/// resumable changefeed that may replace it
+enum ConnRef<'a, S: 'a + Transport> {
+	Borrowed(&'a Connection<S>),
+	Shared(Arc<Connection<S>>),
+}
+
+impl<'a, S: Transport> Deref for ConnRef<'a, S> {
+	type Target = Connection<S>;
+	
+	fn deref(&self) -> &Connection<S> {
+		match *self {
+			ConnRef::Borrowed(conn) => conn,
+			ConnRef::Shared(ref conn) => conn,
+		}
+	}
+}
+
+impl<'a, S: Transport> ResultSet<'a, S> {
+	fn new(conn: ConnRef<'a, S>, token: u64, response: Response, formats: Formats) -> ResultSet<'a, S> {
+		ResultSet {
+			conn: conn,
+			token: token,
+			response_type: response.response_type,
+			buffer: response.data.into_iter().collect(),
+			notes: response.notes,
+			profile: response.profile,
+			formats: formats,
+		}
+	}
+	
+	/// The type of the most recently received response
+	pub fn response_type(&self) -> ResponseType {
+		self.response_type
+	}
+	
+	/// True if the server has more batches available for this query
+	pub fn is_partial(&self) -> bool {
+		self.response_type == ResponseType::SUCCESS_PARTIAL
+	}
+	
+	pub fn notes(&self) -> &[ResponseNote] {
+		&self.notes
+	}
+	
+	pub fn profile(&self) -> Option<&json::Json> {
+		self.profile.as_ref()
+	}
+	
+	fn fetch_batch(&mut self) -> Result<(), QueryError> {
+		let continue_query = json::Json::Array(vec![
+			(QueryType::CONTINUE as u32).to_json(),
+		]);
+		
+		try!(self.conn.send_query_with_token(self.token, &continue_query));
+		let response = try!(Response::from_json(try!(self.conn.recv_response(self.token))));
+		
+		self.response_type = response.response_type;
+		self.buffer.extend(response.data);
+		self.notes = response.notes;
+		Ok(())
+	}
+	
+	/// Tells the server to discard the rest of the results for this query
+	pub fn close(&mut self) -> Result<(), QueryError> {
+		self.buffer.clear();
+		
+		if !self.is_partial() {
+			return Ok(());
+		}
+		
+		let stop_query = json::Json::Array(vec![
+			(QueryType::STOP as u32).to_json(),
+		]);
+		
+		self.response_type = ResponseType::SUCCESS_SEQUENCE;
+		try!(self.conn.send_query_with_token(self.token, &stop_query));
+		try!(Response::from_json(try!(self.conn.recv_response(self.token))));
+		Ok(())
+	}
+}
+
+impl<'a, S: Transport> Iterator for ResultSet<'a, S> {
+	type Item = Result<json::Json, QueryError>;
+	
+	fn next(&mut self) -> Option<Result<json::Json, QueryError>> {
+		loop {
+			if let Some(datum) = self.buffer.pop_front() {
+				return Some(Ok(datum));
+			}
+			
+			if !self.is_partial() {
+				return None;
+			}
+			
+			if let Err(e) = self.fetch_batch() {
+				//the server discards the query state after an error
+				self.response_type = ResponseType::SUCCESS_SEQUENCE;
+				return Some(Err(e));
+			}
+		}
 	}
 }
 
-pub struct ResultSet {
+impl<'a, S: Transport> Drop for ResultSet<'a, S> {
+	fn drop(&mut self) {
+		let _ = self.close();
+		self.conn.release_token(self.token);
+	}
+}
+
+impl<'a, S: Transport> ResultSet<'a, S> {
+	/// Deserializes each result into `T` as it is read
+	pub fn decode<T: DeserializeOwned>(self) -> TypedResultSet<'a, T, S> {
+		TypedResultSet {results: self, marker: PhantomData}
+	}
+	
+	/// Converts each result to a `Datum`, decoding pseudo-types according to the formats the
+	/// query was run with
+	pub fn datums(self) -> Datums<'a, S> {
+		Datums {results: self}
+	}
+}
+
+/// A cursor yielding `Datum`s, created by `ResultSet::datums`
+pub struct Datums<'a, S: 'a + Transport = Socket> {
+	results: ResultSet<'a, S>,
+}
+
+impl<'a, S: Transport> Iterator for Datums<'a, S> {
+	type Item = Result<Datum, QueryError>;
+	
+	fn next(&mut self) -> Option<Result<Datum, QueryError>> {
+		let formats = self.results.formats;
+		self.results.next().map(|result| result.and_then(|datum| {
+			Datum::from_json(datum, &formats).map_err(|e| QueryError::from(ConnectionError::Data(e)))
+		}))
+	}
+}
+
+/// A cursor that deserializes each result, created by `Queryable::run_as` or `ResultSet::decode`
+pub struct TypedResultSet<'a, T, S: 'a + Transport = Socket> {
+	results: ResultSet<'a, S>,
+	marker: PhantomData<T>,
+}
+
+impl<'a, T, S: Transport> TypedResultSet<'a, T, S> {
+	/// The underlying cursor, for its response type, notes and profile
+	pub fn results(&self) -> &ResultSet<'a, S> {
+		&self.results
+	}
+	
+	/// Tells the server to discard the rest of the results for this query
+	pub fn close(&mut self) -> Result<(), QueryError> {
+		self.results.close()
+	}
+}
+
+impl<'a, T: DeserializeOwned, S: Transport> Iterator for TypedResultSet<'a, T, S> {
+	type Item = Result<T, QueryError>;
+	
+	fn next(&mut self) -> Option<Result<T, QueryError>> {
+		self.results.next().map(|result| result.and_then(|datum| {
+			convert::from_json(datum).map_err(|e| QueryError::from(ConnectionError::Data(e)))
+		}))
+	}
 }
 
 #[derive(Copy,Clone)]
@@ -109,7 +598,7 @@ pub struct ClosureVar {
 impl TreeNode for ClosureVar {
 	fn get_reql_json(&self, _state: &mut ReQLGenState) -> json::Json {
 		json::Json::Array(vec![
-			(TermTypes::VAR as u32).to_json(),
+			(TermType::VAR as u32).to_json(),
 			json::Json::Array(vec![self.n.to_json()])
 		])
 	}
@@ -118,36 +607,228 @@ impl TreeNode for ClosureVar {
 impl Value for ClosureVar {}
 
 pub trait Queryable : TreeNode {
-	fn run(self, conn: &mut net::Connection) -> Result<ResultSet, QueryError>
+	fn run<'c, S: Transport>(self, conn: &'c Connection<S>) -> Result<ResultSet<'c, S>, QueryError>
 		where Self: Sized
 	{
-		let mut state = ReQLGenState::new();
-		let unwrapped_query = self.get_reql_json(&mut state);
-		println!("{}", unwrapped_query);
+		self.run_with_formats(conn, Formats::default())
+	}
+	
+	/// Runs the query with the given `time_format`, `binary_format` and `group_format`, which
+	/// `ResultSet::datums` follows when decoding pseudo-types
+	fn run_with_formats<'c, S: Transport>(self, conn: &'c Connection<S>, formats: Formats) -> Result<ResultSet<'c, S>, QueryError>
+		where Self: Sized
+	{
+		let mut global_optargs = BTreeMap::new();
 		
-		let wrapped_query = json::Json::Array(vec![
-			(QueryTypes::START as u32).to_json(),
-			unwrapped_query,
-		]);
+		for &(name, format) in &[("time_format", formats.time), ("binary_format", formats.binary), ("group_format", formats.group)] {
+			if format != Format::Native {
+				global_optargs.insert(name.to_owned(), format.to_json());
+			}
+		}
 		
-		try!(conn.send_query(&wrapped_query));
-		let reply = try!(conn.recv_response());
+		let wrapped_query = start_query(&self, &conn.default_db(), global_optargs);
+		open_cursor(ConnRef::Borrowed(conn), &wrapped_query, formats)
+	}
+	
+	/// Runs the query and deserializes each result into `T`
+	fn run_as<'c, T, S>(self, conn: &'c Connection<S>) -> Result<TypedResultSet<'c, T, S>, QueryError>
+		where
+			Self: Sized,
+			T: DeserializeOwned,
+			S: Transport
+	{
+		self.run(conn).map(ResultSet::decode)
+	}
+	
+	/// Runs a query that produces grouped data, such as `group(...).count()`, and decodes it
+	/// into a map of group key to the value for that group
+	fn run_grouped<K, V, S>(self, conn: &Connection<S>) -> Result<BTreeMap<K, V>, QueryError>
+		where
+			Self: Sized,
+			K: DeserializeOwned + Ord,
+			V: DeserializeOwned,
+			S: Transport
+	{
+		let mut results = try!(self.run(conn));
 		
-		println!("{}", reply.pretty());
+		let obj = match results.next() {
+			Some(result) => try!(result),
+			None => return Err(QueryError::from(ConnectionError::Data(DataError::NoDataReceived))),
+		};
 		
-		Ok(ResultSet {})
+		decode_grouped(obj).map_err(|e| QueryError::from(ConnectionError::Data(e)))
+	}
+	
+	/// Sends the query with the `noreply` optarg and returns without waiting for it to run.
+	/// Errors in the query are not reported; use `Connection::noreply_wait` to wait for it to finish.
+	fn run_noreply<S: Transport>(self, conn: &Connection<S>) -> Result<(), QueryError>
+		where Self: Sized
+	{
+		let global_optargs = btreemap!{
+			"noreply".to_owned() => json::Json::Boolean(true)
+		};
+		
+		try!(conn.send_noreply(&start_query(&self, &conn.default_db(), global_optargs)));
+		Ok(())
+	}
+	
+	/// Starts the query without blocking, resolving to a cursor once the first batch arrives
+	#[cfg(feature = "async")]
+	fn run_async(self, conn: &net::asynchronous::Connection) -> net::asynchronous::RunFuture
+		where Self: Sized
+	{
+		conn.start(start_query(&self, &conn.default_db(), BTreeMap::new()))
 	}
 }
 
+/// Sends a START query and waits for its first batch
+fn open_cursor<'c, S: Transport>(conn: ConnRef<'c, S>, query: &json::Json, formats: Formats) -> Result<ResultSet<'c, S>, QueryError> {
+	let token = try!(conn.send_query(query));
+	let response = conn.recv_response(token).map_err(QueryError::from).and_then(Response::from_json);
+	
+	match response {
+		Ok(response) => Ok(ResultSet::new(conn, token, response, formats)),
+		Err(e) => {
+			conn.release_token(token);
+			Err(e)
+		},
+	}
+}
+
+/// Wraps a term in a START query, adding the connection's default database as the `db` global optarg
+fn start_query<T: TreeNode>(term: &T, default_db: &str, mut global_optargs: BTreeMap<String, json::Json>) -> json::Json {
+	let mut state = ReQLGenState::new();
+	let unwrapped_query = term.get_reql_json(&mut state);
+	
+	global_optargs.insert("db".to_owned(), db(default_db).get_reql_json(&mut state));
+	
+	json::Json::Array(vec![
+		(QueryType::START as u32).to_json(),
+		unwrapped_query,
+		json::Json::Object(global_optargs),
+	])
+}
+
 //Primitives
 impl<T> Value for T where T: json::ToJson {}
 
 impl<T> TreeNode for T where T: json::ToJson {
 	fn get_reql_json(&self, _state: &mut ReQLGenState) -> json::Json {
-		self.to_json()
+		datum_to_term(self.to_json())
+	}
+}
+
+/// Converts a JSON datum into a term. Bare arrays would be interpreted by the server as
+/// `[type, args]` terms, so they are wrapped in MAKE_ARRAY; objects are kept as object literals.
+fn datum_to_term(datum: json::Json) -> json::Json {
+	match datum {
+		json::Json::Array(arr) =>
+			json::Json::Array(vec![
+				(TermType::MAKE_ARRAY as u32).to_json(),
+				json::Json::Array(arr.into_iter().map(datum_to_term).collect())
+			]),
+		json::Json::Object(obj) =>
+			json::Json::Object(obj.into_iter().map(|(k, v)| (k, datum_to_term(v))).collect()),
+		datum => datum,
+	}
+}
+
+//Expressions
+
+/// A Rust value embedded in a query
+pub struct Expr {
+	datum: json::Json,
+}
+
+impl TreeNode for Expr {
+	fn get_reql_json(&self, _state: &mut ReQLGenState) -> json::Json {
+		datum_to_term(self.datum.clone())
+	}
+}
+
+impl Value for Expr {}
+
+impl Queryable for Expr {}
+
+/// Embeds any serde-serializable value in a query.
+///
+/// Panics if the value has no JSON representation, such as a map with non-string keys;
+/// use `try_expr` to handle that case.
+pub fn expr<T: Serialize + ?Sized>(val: &T) -> Expr {
+	match try_expr(val) {
+		Ok(expr) => expr,
+		Err(e) => panic!("{}", e),
 	}
 }
 
+/// Embeds a value that implements `rustc_serialize`'s `ToJson`, such as a `Json` tree, in a query
+pub fn expr_json<T: ToJson + ?Sized>(val: &T) -> Expr {
+	Expr {datum: val.to_json()}
+}
+
+/// Embeds any serde-serializable value in a query. Fails if the value has no JSON
+/// representation, such as a map with non-string keys.
+pub fn try_expr<T: Serialize + ?Sized>(val: &T) -> Result<Expr, DataError> {
+	convert::to_json(val)
+		.map(|datum| Expr {datum: datum})
+		.map_err(|e| DataError::Encode(e.to_string()))
+}
+
+/// An array whose elements may be arbitrary terms
+pub struct Array<'a> {
+	items: Vec<Box<TreeNode + 'a>>,
+}
+
+impl<'a> Array<'a> {
+	pub fn push<T: TreeNode + 'a>(mut self, val: T) -> Array<'a> {
+		self.items.push(Box::new(val));
+		self
+	}
+}
+
+impl<'a> TreeNode for Array<'a> {
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		json::Json::Array(vec![
+			(TermType::MAKE_ARRAY as u32).to_json(),
+			json::Json::Array(self.items.iter().map(|item| item.get_reql_json(state)).collect())
+		])
+	}
+}
+
+impl<'a> Value for Array<'a> {}
+
+impl<'a> Queryable for Array<'a> {}
+
+pub fn array<'a>() -> Array<'a> {
+	Array {items: Vec::new()}
+}
+
+/// An object literal whose fields may be arbitrary terms
+pub struct Object<'a> {
+	fields: BTreeMap<String, Box<TreeNode + 'a>>,
+}
+
+impl<'a> Object<'a> {
+	pub fn insert<T: TreeNode + 'a>(mut self, key: &str, val: T) -> Object<'a> {
+		self.fields.insert(key.to_owned(), Box::new(val));
+		self
+	}
+}
+
+impl<'a> TreeNode for Object<'a> {
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		json::Json::Object(self.fields.iter().map(|(k, v)| (k.clone(), v.get_reql_json(state))).collect())
+	}
+}
+
+impl<'a> Value for Object<'a> {}
+
+impl<'a> Queryable for Object<'a> {}
+
+pub fn object<'a>() -> Object<'a> {
+	Object {fields: BTreeMap::new()}
+}
+
 //Predicates
 
 impl TreeNode for Fn(&ClosureVar) -> bool {
@@ -156,32 +837,403 @@ impl TreeNode for Fn(&ClosureVar) -> bool {
 	}
 }
 
-//Selection
+//Stream
 
-pub trait Selection : TreeNode {
+/// A sequence of values. Transformations that keep a selection's documents intact, such as
+/// `order_by` and `limit`, return a `Selection` when applied to one.
+pub trait Stream : TreeNode {
+	/// The elements for which `predicate` returns true
 	fn filter_fn<P, T>(self, predicate: P) -> Filter<Self, P, T>
 		where 
 			P: Fn(ClosureVar) -> T,
 			T: TreeNode,
 			Self: Sized
 	{
-		Filter {source: self, predicate: predicate}
+		Filter {source: self, predicate: Func::new(predicate)}
+	}
+	
+	/// Transforms each element with a function
+	fn map<P, T>(self, func: P) -> Map<Self, P>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Map::new(TermType::MAP, self, func)
+	}
+	
+	/// Transforms each element into a sequence, and concatenates the results
+	fn concat_map<P, T>(self, func: P) -> Map<Self, P>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Map::new(TermType::CONCAT_MAP, self, func)
+	}
+	
+	/// Sorts by the given keys, e.g. `order_by(vec![r::desc("score"), r::asc("name")])`
+	fn order_by<'a, I>(self, keys: I) -> OrderBy<'a, Self>
+		where I: IntoIterator, I::Item: Into<OrderKey>, Self: Sized
+	{
+		OrderBy::new(self, keys)
+	}
+	
+	/// The first `n` elements
+	fn limit(self, n: u64) -> Limit<Self>
+		where Self: Sized
+	{
+		Limit::new(TermType::LIMIT, self, n)
+	}
+	
+	/// All but the first `n` elements
+	fn skip(self, n: u64) -> Limit<Self>
+		where Self: Sized
+	{
+		Limit::new(TermType::SKIP, self, n)
+	}
+	
+	/// `n` elements chosen at random
+	fn sample(self, n: u64) -> Limit<Self>
+		where Self: Sized
+	{
+		Limit::new(TermType::SAMPLE, self, n)
+	}
+	
+	/// The elements from `start` up to `end`, or to the end of the sequence if `end` is `None`.
+	/// Negative positions count from the end.
+	fn slice<'a>(self, start: i64, end: Option<i64>) -> Slice<'a, Self>
+		where Self: Sized
+	{
+		Slice::new(self, start, end)
+	}
+	
+	/// The element at `index`, counting from the end if negative
+	fn nth(self, index: i64) -> Nth<Self>
+		where Self: Sized
+	{
+		Nth::new(self, index)
+	}
+	
+	/// True if the sequence has no elements
+	fn is_empty(self) -> IsEmpty<Self>
+		where Self: Sized
+	{
+		IsEmpty::new(self)
+	}
+	
+	/// The elements of this sequence and `other`
+	fn union<'a, T>(self, other: T) -> Union<'a, Self, T>
+		where T: Stream, Self: Sized
+	{
+		Union::new(self, other)
+	}
+	
+	/// The elements with duplicates removed, in sorted order
+	fn distinct(self) -> Distinct<Self>
+		where Self: Sized
+	{
+		Distinct::new(self)
+	}
+	
+	/// Pairs each element with the document of `table` whose primary key equals the element's
+	/// `left_field`. Elements without a match are left out.
+	fn eq_join<'a>(self, left_field: &str, table: Table<'a>) -> EqJoin<'a, Self, String>
+		where Self: Sized
+	{
+		EqJoin::new(self, left_field.to_owned(), table)
+	}
+	
+	/// Pairs each element with the document of `table` whose primary key equals the result of a
+	/// function of the element
+	fn eq_join_fn<'a, P, T>(self, func: P, table: Table<'a>) -> EqJoin<'a, Self, Func<P>>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		EqJoin::new(self, Func::new(func), table)
+	}
+	
+	/// Pairs each element with every element of `other` for which `predicate` returns true.
+	/// The predicate is called with the left and the right element.
+	fn inner_join<T, P, U>(self, other: T, predicate: P) -> Join<Self, T, P>
+		where T: Stream, P: Fn(ClosureVar, ClosureVar) -> U, U: TreeNode, Self: Sized
+	{
+		Join::new(TermType::INNER_JOIN, self, other, predicate)
+	}
+	
+	/// Like `inner_join`, but elements with no match in `other` are kept without a right element
+	fn outer_join<T, P, U>(self, other: T, predicate: P) -> Join<Self, T, P>
+		where T: Stream, P: Fn(ClosureVar, ClosureVar) -> U, U: TreeNode, Self: Sized
+	{
+		Join::new(TermType::OUTER_JOIN, self, other, predicate)
+	}
+	
+	/// Merges the left and right elements of a join into one document
+	fn zip(self) -> Zip<Self>
+		where Self: Sized
+	{
+		Zip::new(self)
+	}
+	
+	/// Keeps only the selected fields of each element
+	fn pluck<I>(self, fields: I) -> Fields<Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Fields::new(TermType::PLUCK, self, fields)
+	}
+	
+	/// Removes the selected fields from each element
+	fn without<I>(self, fields: I) -> Fields<Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Fields::new(TermType::WITHOUT, self, fields)
+	}
+	
+	/// The elements that have all of the selected fields, and they are not null
+	fn has_fields<I>(self, fields: I) -> Fields<Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Fields::new(TermType::HAS_FIELDS, self, fields)
+	}
+	
+	/// The elements that have all of the selected fields, with only those fields
+	fn with_fields<I>(self, fields: I) -> Fields<Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Fields::new(TermType::WITH_FIELDS, self, fields)
+	}
+	
+	/// Merges `other` into each element
+	fn merge<T>(self, other: T) -> Merge<Self, T>
+		where T: Value, Self: Sized
+	{
+		Merge::new(self, other)
+	}
+	
+	/// Merges the result of a function of each element into it
+	fn merge_fn<P, T>(self, func: P) -> Merge<Self, Func<P>>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Merge::new(self, Func::new(func))
+	}
+	
+	/// Groups the elements by the values of one or more fields, which may be nested, e.g.
+	/// `group(vec![r::nested("address", vec!["country"])])`
+	fn group<'a, I>(self, fields: I) -> Group<'a, Self>
+		where I: IntoIterator, I::Item: Into<Selector>, Self: Sized
+	{
+		Group::from_fields(self, fields)
+	}
+	
+	/// Groups the elements by the result of a function
+	fn group_fn<'a, P, T>(self, func: P) -> Group<'a, Self>
+		where P: Fn(ClosureVar) -> T + 'a, T: TreeNode + 'a, Self: Sized
+	{
+		Group::new(self, vec![Box::new(Func::<P>::new(func))])
+	}
+	
+	/// Turns the groups of a `group` into a sequence of objects with `group` and `reduction` fields
+	fn ungroup(self) -> Ungroup<Self>
+		where Self: Sized
+	{
+		Ungroup::new(self)
+	}
+	
+	/// Combines the elements into one value with a function of two elements, or of two earlier
+	/// results. The elements may be combined in any order.
+	fn reduce<P, T>(self, func: P) -> Reduce<Self, P>
+		where P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Reduce::new(self, func)
+	}
+	
+	/// Combines the elements in order into one value with a function of the accumulator and
+	/// each element, starting from `base`
+	fn fold<'a, B, P, T>(self, base: B, func: P) -> Fold<'a, Self, B, P>
+		where B: Value, P: Fn(ClosureVar, ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Fold::new(self, base, func)
+	}
+	
+	/// The number of elements
+	fn count<'a>(self) -> Aggregate<'a, Self>
+		where Self: Sized
+	{
+		Aggregate::new(TermType::COUNT, self, None)
+	}
+	
+	/// The number of elements equal to `val`
+	fn count_value<'a, T>(self, val: T) -> Aggregate<'a, Self>
+		where T: Value + 'a, Self: Sized
+	{
+		Aggregate::new(TermType::COUNT, self, Some(Box::new(val)))
+	}
+	
+	/// The number of elements for which `predicate` returns true
+	fn count_fn<'a, P, T>(self, predicate: P) -> Aggregate<'a, Self>
+		where P: Fn(ClosureVar) -> T + 'a, T: TreeNode + 'a, Self: Sized
+	{
+		Aggregate::new(TermType::COUNT, self, Some(Box::new(Func::<P>::new(predicate))))
+	}
+	
+	/// The sum of a field of each element
+	fn sum<'a>(self, field: &str) -> Aggregate<'a, Self>
+		where Self: Sized
+	{
+		Aggregate::new(TermType::SUM, self, Some(Box::new(field.to_owned())))
+	}
+	
+	/// The sum of a function of each element
+	fn sum_fn<'a, P, T>(self, func: P) -> Aggregate<'a, Self>
+		where P: Fn(ClosureVar) -> T + 'a, T: TreeNode + 'a, Self: Sized
+	{
+		Aggregate::new(TermType::SUM, self, Some(Box::new(Func::<P>::new(func))))
+	}
+	
+	/// The average of a field of each element
+	fn avg<'a>(self, field: &str) -> Aggregate<'a, Self>
+		where Self: Sized
+	{
+		Aggregate::new(TermType::AVG, self, Some(Box::new(field.to_owned())))
+	}
+	
+	/// The average of a function of each element
+	fn avg_fn<'a, P, T>(self, func: P) -> Aggregate<'a, Self>
+		where P: Fn(ClosureVar) -> T + 'a, T: TreeNode + 'a, Self: Sized
+	{
+		Aggregate::new(TermType::AVG, self, Some(Box::new(Func::<P>::new(func))))
+	}
+	
+	/// The element with the smallest value in a field. Use `Table::min_index` to use an index.
+	fn min<'a>(self, field: &str) -> MinMax<'a, Self>
+		where Self: Sized
+	{
+		MinMax::new(TermType::MIN, self, Some(Box::new(field.to_owned())))
+	}
+	
+	/// The element for which a function returns the smallest value
+	fn min_fn<'a, P, T>(self, func: P) -> MinMax<'a, Self>
+		where P: Fn(ClosureVar) -> T + 'a, T: TreeNode + 'a, Self: Sized
+	{
+		MinMax::new(TermType::MIN, self, Some(Box::new(Func::<P>::new(func))))
+	}
+	
+	/// The element with the largest value in a field. Use `Table::max_index` to use an index.
+	fn max<'a>(self, field: &str) -> MinMax<'a, Self>
+		where Self: Sized
+	{
+		MinMax::new(TermType::MAX, self, Some(Box::new(field.to_owned())))
+	}
+	
+	/// The element for which a function returns the largest value
+	fn max_fn<'a, P, T>(self, func: P) -> MinMax<'a, Self>
+		where P: Fn(ClosureVar) -> T + 'a, T: TreeNode + 'a, Self: Sized
+	{
+		MinMax::new(TermType::MAX, self, Some(Box::new(Func::<P>::new(func))))
 	}
 }
 
-impl<T> Queryable for T where T: Selection {}
+//Selection
+
+/// A sequence of documents that can be updated or deleted, such as a table or the result
+/// of `get_all`, `between` or `filter_fn` on another selection
+pub trait Selection : Stream {
+	/// Updates every document in the selection by merging in `doc`
+	fn update<'a, T>(self, doc: T) -> Update<'a, Self, T>
+		where T: Value, Self: Sized
+	{
+		Update::new(self, doc)
+	}
+	
+	/// Updates every document in the selection using a function of the old document
+	fn update_fn<'a, P, T>(self, func: P) -> Update<'a, Self, Func<P>>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Update::new(self, Func::new(func))
+	}
+	
+	/// Replaces every document in the selection with `doc`
+	fn replace<'a, T>(self, doc: T) -> Replace<'a, Self, T>
+		where T: Value, Self: Sized
+	{
+		Replace::new(self, doc)
+	}
+	
+	/// Replaces every document in the selection using a function of the old document
+	fn replace_fn<'a, P, T>(self, func: P) -> Replace<'a, Self, Func<P>>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Replace::new(self, Func::new(func))
+	}
+	
+	/// Deletes every document in the selection
+	fn delete<'a>(self) -> Delete<'a, Self>
+		where Self: Sized
+	{
+		Delete::new(self)
+	}
+	
+	/// Subscribes to changes to the documents in the selection
+	fn changes<'a>(self) -> Changes<'a, Self>
+		where Self: Sized
+	{
+		Changes::new(self)
+	}
+}
+
+/// A selection of at most one document, such as the result of `get`
+pub trait SingleSelection : TreeNode {
+	fn update<'a, T>(self, doc: T) -> Update<'a, Self, T>
+		where T: Value, Self: Sized
+	{
+		Update::new(self, doc)
+	}
+	
+	fn update_fn<'a, P, T>(self, func: P) -> Update<'a, Self, Func<P>>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Update::new(self, Func::new(func))
+	}
+	
+	fn replace<'a, T>(self, doc: T) -> Replace<'a, Self, T>
+		where T: Value, Self: Sized
+	{
+		Replace::new(self, doc)
+	}
+	
+	fn replace_fn<'a, P, T>(self, func: P) -> Replace<'a, Self, Func<P>>
+		where P: Fn(ClosureVar) -> T, T: TreeNode, Self: Sized
+	{
+		Replace::new(self, Func::new(func))
+	}
+	
+	fn delete<'a>(self) -> Delete<'a, Self>
+		where Self: Sized
+	{
+		Delete::new(self)
+	}
+	
+	/// Subscribes to changes to the document, which need not exist yet
+	fn changes<'a>(self) -> Changes<'a, Self>
+		where Self: Sized
+	{
+		Changes::new(self)
+	}
+}
 
 //Filter
+
+/// The elements of a sequence for which a predicate returns true
 pub struct Filter<S, P, T>
 	where
-		S: Selection,
+		S: Stream,
 		P: Fn(ClosureVar) -> T,
 		T: TreeNode
 {
 	source: S,
-	predicate: P,
+	predicate: Func<P>,
 }
 
+impl<S, P, T> Stream for Filter<S, P, T>
+	where
+		S: Stream,
+		P: Fn(ClosureVar) -> T,
+		T: TreeNode
+{}
+
 impl<S, P, T> Selection for Filter<S, P, T>
 	where
 		S: Selection,
@@ -189,33 +1241,23 @@ impl<S, P, T> Selection for Filter<S, P, T>
 		T: TreeNode
 {}
 
+impl<S, P, T> Queryable for Filter<S, P, T>
+	where
+		S: Stream,
+		P: Fn(ClosureVar) -> T,
+		T: TreeNode
+{}
+
 impl<S, P, T> TreeNode for Filter<S, P, T>
 	where
-		S: Selection,
+		S: Stream,
 		P: Fn(ClosureVar) -> T,
 		T: TreeNode
 {
 	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
-		let cv = state.gen_closure_var();
-		
-		let func_call = json::Json::Array(vec![
-			(TermTypes::FUNC as u32).to_json(),
-			json::Json::Array(vec![
-				json::Json::Array(vec![
-					(TermTypes::MAKE_ARRAY as u32).to_json(),
-					json::Json::Array(vec![
-						cv.n.to_json()
-					])
-				]),
-				(self.predicate)(cv).get_reql_json(state)
-			])
-		]);
-		
-		json::Json::Array(vec![
-			(TermTypes::FILTER as u32).to_json(),
-			self.source.get_reql_json(state),
-			func_call
-		])
+		let source = self.source.get_reql_json(state);
+		let predicate = self.predicate.get_reql_json(state);
+		make_term(TermType::FILTER, vec![source, predicate], None)
 	}
 }
 
@@ -238,7 +1280,7 @@ pub fn db(db_name: &str) -> DB {
 impl<'a> TreeNode for DB<'a> {
 	fn get_reql_json(&self, _state: &mut ReQLGenState) -> json::Json {
 		json::Json::Array(vec![
-			(TermTypes::DB as u32).to_json(),
+			(TermType::DB as u32).to_json(),
 			json::Json::Array(vec![
 				self.name.to_json()
 			])
@@ -254,8 +1296,31 @@ pub struct Table<'a> {
 }
 
 impl<'a> Table<'a> {
+	/// Inserts a document or an array of documents
+	pub fn insert<T: Value>(self, docs: T) -> Insert<'a, T> {
+		Insert::new(self, docs)
+	}
+	
+	/// Looks up a single document by primary key
+	pub fn get<K: Value>(self, key: K) -> Get<'a, K> {
+		Get::new(self, key)
+	}
+	
+	/// Looks up all documents matching any of `keys`
+	pub fn get_all<K: Value>(self, keys: Vec<K>) -> GetAll<'a, K> {
+		GetAll::new(self, keys)
+	}
+	
+	/// Selects all documents with a key between `lower` (inclusive) and `upper` (exclusive)
+	pub fn between<L: Value, U: Value>(self, lower: L, upper: U) -> Between<'a, L, U> {
+		Between::new(self, lower, upper)
+	}
 }
 
+impl<'a> Stream for Table<'a> {}
+
+impl<'a> Queryable for Table<'a> {}
+
 impl<'a> Selection for Table<'a> {
 }
 
@@ -264,7 +1329,7 @@ impl<'a> TreeNode for Table<'a> {
 		match self.db {
 			Some(db) =>
 				json::Json::Array(vec![
-					(TermTypes::TABLE as u32).to_json(),
+					(TermType::TABLE as u32).to_json(),
 					json::Json::Array(vec![
 						db.get_reql_json(state),
 						self.name.to_json()
@@ -272,7 +1337,7 @@ impl<'a> TreeNode for Table<'a> {
 				]),
 			None =>
 				json::Json::Array(vec![
-					(TermTypes::TABLE as u32).to_json(),
+					(TermType::TABLE as u32).to_json(),
 					json::Json::Array(vec![
 						self.name.to_json()
 					])
@@ -288,9 +1353,37 @@ pub fn table(name: &str) -> Table {
 #[cfg(test)]
 mod tests {
 	use super::prelude::*;
+	use super::{Response, ResponseType};
+	use err::{QueryError, Frame, ErrorType};
+	use rustc_serialize::json::{Json, ToJson};
 	
 	#[test]
     fn it_works() {
 		
     }
+	
+	#[test]
+	fn test_parse_sequence() {
+		let reply = Json::from_str("{\"t\":2,\"r\":[{\"id\":1},{\"id\":2}],\"n\":[]}").unwrap();
+		let rs = Response::from_json(reply).unwrap();
+		
+		assert_eq!(rs.response_type(), ResponseType::SUCCESS_SEQUENCE);
+		assert!(!rs.is_partial());
+		assert_eq!(rs.data().len(), 2);
+		assert_eq!(rs.data()[1].find("id"), Some(&2u64.to_json()));
+	}
+	
+	#[test]
+	fn test_parse_error() {
+		let reply = Json::from_str("{\"t\":18,\"r\":[\"Table `test.foo` does not exist.\"],\"b\":[1,\"index\"],\"e\":3100000}").unwrap();
+		
+		match Response::from_json(reply) {
+			Err(QueryError::RuntimeError(e)) => {
+				assert_eq!(e.message, "Table `test.foo` does not exist.");
+				assert_eq!(e.backtrace, vec![Frame::Pos(1), Frame::Opt(String::from("index"))]);
+				assert_eq!(e.error_type, Some(ErrorType::NON_EXISTENCE));
+			},
+			_ => panic!("expected a runtime error"),
+		}
+	}
 }
@@ -0,0 +1,142 @@
+use rustc_serialize::json;
+use ql2::TermType;
+use super::{TreeNode, Value, Queryable, ReQLGenState, ClosureVar, Expr, Array, Object, make_term};
+
+/// Implements the `std::ops` operators for a term type in terms of the `Value` methods, so that
+/// `a + b`, `a & b` and `!a` build the same terms as `a.add(b)`, `a.and(b)` and `a.not()`.
+/// Generic parameters are passed in brackets, e.g. `value_ops!([A: Value,] Unary<A>)`.
+macro_rules! value_ops {
+	(@binary [$($gen:tt)*] $ty:ty, $op:ident, $method:ident) => {
+		value_ops!(@binary [$($gen)*] $ty, $op, $method, $method);
+	};
+	(@binary [$($gen:tt)*] $ty:ty, $op:ident, $method:ident, $value_method:ident) => {
+		impl<$($gen)* R: $crate::Value> ::std::ops::$op<R> for $ty {
+			type Output = $crate::math::Binary<$ty, R>;
+			
+			fn $method(self, rhs: R) -> $crate::math::Binary<$ty, R> {
+				$crate::Value::$value_method(self, rhs)
+			}
+		}
+	};
+	([$($gen:tt)*] $ty:ty) => {
+		value_ops!(@binary [$($gen)*] $ty, Add, add);
+		value_ops!(@binary [$($gen)*] $ty, Sub, sub);
+		value_ops!(@binary [$($gen)*] $ty, Mul, mul);
+		value_ops!(@binary [$($gen)*] $ty, Div, div);
+		value_ops!(@binary [$($gen)*] $ty, Rem, rem);
+		value_ops!(@binary [$($gen)*] $ty, BitAnd, bitand, and);
+		value_ops!(@binary [$($gen)*] $ty, BitOr, bitor, or);
+		
+		impl<$($gen)*> ::std::ops::Not for $ty {
+			type Output = $crate::math::Unary<$ty>;
+			
+			fn not(self) -> $crate::math::Unary<$ty> {
+				$crate::Value::not(self)
+			}
+		}
+	};
+	($ty:ty) => {
+		value_ops!([] $ty);
+	};
+}
+
+/// A term applying an operator to two values, created by the comparison, boolean and
+/// arithmetic methods on `Value`, and by field access with `get_field` and `bracket`
+pub struct Binary<A, B>
+	where A: Value, B: Value
+{
+	term_type: TermType,
+	a: A,
+	b: B,
+}
+
+impl<A, B> Binary<A, B>
+	where A: Value, B: Value
+{
+	pub fn new(term_type: TermType, a: A, b: B) -> Binary<A, B> {
+		Binary {term_type: term_type, a: a, b: b}
+	}
+}
+
+impl<A, B> TreeNode for Binary<A, B>
+	where A: Value, B: Value
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let a = self.a.get_reql_json(state);
+		let b = self.b.get_reql_json(state);
+		make_term(self.term_type, vec![a, b], None)
+	}
+}
+
+impl<A, B> Value for Binary<A, B> where A: Value, B: Value {}
+
+impl<A, B> Queryable for Binary<A, B> where A: Value, B: Value {}
+
+/// A term applying an operator to a single value, such as `not`, `floor` or `keys`
+pub struct Unary<A>
+	where A: Value
+{
+	term_type: TermType,
+	a: A,
+}
+
+impl<A> Unary<A>
+	where A: Value
+{
+	pub fn new(term_type: TermType, a: A) -> Unary<A> {
+		Unary {term_type: term_type, a: a}
+	}
+}
+
+impl<A> TreeNode for Unary<A>
+	where A: Value
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let a = self.a.get_reql_json(state);
+		make_term(self.term_type, vec![a], None)
+	}
+}
+
+impl<A> Value for Unary<A> where A: Value {}
+
+impl<A> Queryable for Unary<A> where A: Value {}
+
+/// One of two values chosen by a condition, created by `r::branch`
+pub struct Branch<C, A, B>
+	where C: Value, A: Value, B: Value
+{
+	test: C,
+	if_true: A,
+	if_false: B,
+}
+
+impl<C, A, B> TreeNode for Branch<C, A, B>
+	where C: Value, A: Value, B: Value
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let test = self.test.get_reql_json(state);
+		let if_true = self.if_true.get_reql_json(state);
+		let if_false = self.if_false.get_reql_json(state);
+		make_term(TermType::BRANCH, vec![test, if_true, if_false], None)
+	}
+}
+
+impl<C, A, B> Value for Branch<C, A, B> where C: Value, A: Value, B: Value {}
+
+impl<C, A, B> Queryable for Branch<C, A, B> where C: Value, A: Value, B: Value {}
+
+/// `if_true` if `test` holds and `if_false` otherwise, e.g.
+/// `r::branch(user.get_field("age").ge(18), "adult", "minor")`
+pub fn branch<C, A, B>(test: C, if_true: A, if_false: B) -> Branch<C, A, B>
+	where C: Value, A: Value, B: Value
+{
+	Branch {test: test, if_true: if_true, if_false: if_false}
+}
+
+value_ops!([A: Value, B: Value,] Binary<A, B>);
+value_ops!([A: Value,] Unary<A>);
+value_ops!([C: Value, A: Value, B: Value,] Branch<C, A, B>);
+value_ops!(ClosureVar);
+value_ops!(Expr);
+value_ops!(['a,] Array<'a>);
+value_ops!(['a,] Object<'a>);
@@ -0,0 +1,547 @@
+//! A non-blocking counterpart to `Connection` for use on a tokio runtime, enabled with the
+//! `async` feature. It shares the wire format and SCRAM handshake with the blocking API.
+
+use futures::{Future, Stream, Poll, Async};
+use futures::future::{self, Loop};
+use futures::sync::mpsc;
+use tokio;
+use tokio::io::{self as aio, AsyncRead, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::timer::{timeout, Timeout};
+use rustc_serialize::json::{self, ToJson};
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use err::{ConnectionError, DataError, QueryError};
+use ql2::{QueryType, ResponseType, ResponseNote};
+use Response;
+use super::{scram, ConnectionBuilder, ServerVersion, ServerInfo, HANDSHAKE_MAGIC, FRAME_HEADER_LEN, encode_frame, decode_frame, encode_packet, decode_packet};
+
+pub type ConnectFuture = Box<Future<Item=Connection, Error=ConnectionError> + Send>;
+pub type RunFuture = Box<Future<Item=Cursor, Error=QueryError> + Send>;
+
+type BoxFuture<T> = Box<Future<Item=T, Error=ConnectionError> + Send>;
+
+/// Senders for the responses to each query in flight, or `None` once the socket has closed
+type Routes = Arc<Mutex<Option<HashMap<u64, mpsc::UnboundedSender<Result<json::Json, DataError>>>>>>;
+
+fn closed() -> ConnectionError {
+	ConnectionError::Io(io::Error::new(io::ErrorKind::BrokenPipe, "The connection is closed"))
+}
+
+//handshake
+
+fn send_packet(stream: TcpStream, packet: &json::Json) -> BoxFuture<TcpStream> {
+	Box::new(aio::write_all(stream, encode_packet(packet))
+		.map(|(stream, _)| stream)
+		.map_err(ConnectionError::from))
+}
+
+/// Reads one null terminated handshake message. The server waits for a reply after each
+/// message, so nothing is lost when the buffer is discarded.
+fn recv_packet(stream: TcpStream) -> BoxFuture<(TcpStream, json::Json)> {
+	Box::new(aio::read_until(BufReader::new(stream), 0x00, Vec::new())
+		.map_err(ConnectionError::from)
+		.and_then(|(br, buf)| {
+			let packet = try!(decode_packet(&buf));
+			Ok((br.into_inner(), packet))
+		}))
+}
+
+fn handshake(stream: TcpStream, user: String, pass: String) -> BoxFuture<(TcpStream, ServerVersion)> {
+	Box::new(aio::write_all(stream, HANDSHAKE_MAGIC)
+		.map_err(ConnectionError::from)
+		.and_then(|(stream, _)| recv_packet(stream))
+		.and_then(move |(stream, reply)| {
+			let version = try!(ConnectionBuilder::validate_server_reply(&reply));
+			let (packet, hs_a) = try!(scram::begin_handshake(&user, &pass));
+			Ok((stream, packet, (version, hs_a)))
+		})
+		.and_then(|(stream, packet, state)| send_packet(stream, &packet).map(move |stream| (stream, state)))
+		.and_then(|(stream, state)| recv_packet(stream).map(move |(stream, packet)| (stream, packet, state)))
+		.and_then(|(stream, packet, (version, hs_a))| {
+			let (packet, hs_b) = try!(hs_a.handshake_b(&packet));
+			Ok((stream, packet, (version, hs_b)))
+		})
+		.and_then(|(stream, packet, state)| send_packet(stream, &packet).map(move |stream| (stream, state)))
+		.and_then(|(stream, state)| recv_packet(stream).map(move |(stream, packet)| (stream, packet, state)))
+		.and_then(|(stream, packet, (version, hs_b))| {
+			try!(hs_b.handshake_c(&packet));
+			Ok((stream, version))
+		}))
+}
+
+/// Tries each address in turn until one accepts the connection
+fn connect_any(addrs: Vec<SocketAddr>) -> BoxFuture<TcpStream> {
+	let attempt = addrs.into_iter().fold(None, |prev: Option<BoxFuture<TcpStream>>, addr| {
+		let next: BoxFuture<TcpStream> = match prev {
+			Some(prev) => Box::new(prev.or_else(move |_| TcpStream::connect(&addr).map_err(ConnectionError::from))),
+			None => Box::new(TcpStream::connect(&addr).map_err(ConnectionError::from)),
+		};
+		Some(next)
+	});
+	
+	attempt.unwrap_or_else(|| Box::new(future::err(ConnectionError::Io(io::Error::new(io::ErrorKind::NotFound, "The hostname did not resolve to any addresses")))))
+}
+
+fn unsupported(msg: &str) -> ConnectionError {
+	ConnectionError::Io(io::Error::new(io::ErrorKind::Other, msg))
+}
+
+fn timeout_error(e: timeout::Error<ConnectionError>) -> ConnectionError {
+	if e.is_elapsed() {
+		return ConnectionError::Timeout;
+	}
+	
+	match e.into_inner() {
+		Some(inner) => inner,
+		None => ConnectionError::Io(io::Error::new(io::ErrorKind::Other, "The timer failed")),
+	}
+}
+
+impl ConnectionBuilder {
+	/// Connects and authenticates without blocking, within the builder's handshake timeout.
+	///
+	/// The connection's reader and writer are spawned as tasks, so the returned future must be
+	/// run on a tokio runtime. TLS and the read and write timeouts are not yet supported by the
+	/// async API, and the future fails if any of them are set; use tokio's timers to bound
+	/// individual queries instead.
+	pub fn connect_async(self) -> ConnectFuture {
+		if self.tls.is_some() {
+			return Box::new(future::err(unsupported("TLS is not supported by the async API")));
+		}
+		
+		if self.read_timeout.is_some() || self.write_timeout.is_some() {
+			return Box::new(future::err(unsupported("Read and write timeouts are not supported by the async API")));
+		}
+		
+		let addrs = match (self.hostname.as_str(), self.port).to_socket_addrs() {
+			Ok(addrs) => addrs.collect(),
+			Err(e) => return Box::new(future::err(ConnectionError::from(e))),
+		};
+		
+		let ConnectionBuilder {dbname, user, pass, timeout, ..} = self;
+		
+		let conn = connect_any(addrs)
+			.and_then(|stream| {
+				try!(stream.set_nodelay(true));
+				Ok(stream)
+			})
+			.and_then(move |stream| handshake(stream, user, pass))
+			.map(move |(stream, version)| Connection::new(stream, version, dbname));
+		
+		Box::new(Timeout::new(conn, Duration::from_secs(timeout as u64)).map_err(timeout_error))
+	}
+}
+
+//tasks
+
+/// Reads responses until the socket closes, handing each to the cursor waiting on its token
+fn read_loop(reader: ReadHalf<TcpStream>, routes: Routes) -> Box<Future<Item=(), Error=()> + Send> {
+	let frame_routes = routes.clone();
+	
+	let frames = future::loop_fn(reader, move |reader| {
+		let routes = frame_routes.clone();
+		
+		aio::read_exact(reader, [0u8; FRAME_HEADER_LEN])
+			.and_then(|(reader, header)| {
+				let token = BigEndian::read_u64(&header[0..8]);
+				let length = LittleEndian::read_u32(&header[8..12]) as usize;
+				aio::read_exact(reader, vec![0u8; length]).map(move |(reader, body)| (reader, token, body))
+			})
+			.map(move |(reader, token, body)| {
+				if let Some(ref routes) = *routes.lock().unwrap() {
+					if let Some(sender) = routes.get(&token) {
+						let _ = sender.unbounded_send(decode_frame(&body));
+					}
+				}
+				Loop::<(), _>::Continue(reader)
+			})
+	});
+	
+	Box::new(frames.then(move |_| -> Result<(), ()> {
+		//dropping the senders ends every open cursor with an error
+		routes.lock().unwrap().take();
+		Ok(())
+	}))
+}
+
+/// Writes queued frames in order, closing the write side once every `Connection` and `Cursor`
+/// has been dropped
+fn write_loop(writer: WriteHalf<TcpStream>, frames: mpsc::UnboundedReceiver<Vec<u8>>) -> Box<Future<Item=(), Error=()> + Send> {
+	Box::new(frames
+		.fold(writer, |writer, frame| aio::write_all(writer, frame).map(|(writer, _)| writer).map_err(|_| ()))
+		.and_then(|writer| aio::shutdown(writer).map_err(|_| ()))
+		.map(|_| ()))
+}
+
+//connection
+
+/// An authenticated connection driven by tasks on the tokio runtime.
+///
+/// Clones share the same socket and default database, and any number of queries can be in
+/// flight at once.
+#[derive(Clone)]
+pub struct Connection {
+	writer: mpsc::UnboundedSender<Vec<u8>>,
+	routes: Routes,
+	next_token: Arc<AtomicUsize>,
+	db: Arc<RwLock<String>>,
+	server_version: Arc<ServerVersion>,
+}
+
+impl Connection {
+	fn new(stream: TcpStream, server_version: ServerVersion, db: String) -> Connection {
+		let (reader, writer) = stream.split();
+		let routes = Arc::new(Mutex::new(Some(HashMap::new())));
+		let (frames_tx, frames_rx) = mpsc::unbounded();
+		
+		tokio::spawn(read_loop(reader, routes.clone()));
+		tokio::spawn(write_loop(writer, frames_rx));
+		
+		Connection {
+			writer: frames_tx,
+			routes: routes,
+			next_token: Arc::new(AtomicUsize::new(0)),
+			db: Arc::new(RwLock::new(db)),
+			server_version: Arc::new(server_version),
+		}
+	}
+	
+	/// The server release and protocol versions reported during the handshake
+	pub fn server_version(&self) -> &ServerVersion {
+		&self.server_version
+	}
+	
+	/// Asks the server which cluster node this connection is attached to
+	pub fn server(&self) -> Box<Future<Item=ServerInfo, Error=QueryError> + Send> {
+		let query = json::Json::Array(vec![
+			json::Json::U64(QueryType::SERVER_INFO as u64),
+		]);
+		
+		Box::new(self.start(query).and_then(|mut cursor| {
+			let info = match (cursor.response_type(), cursor.buffer.pop_front()) {
+				(ResponseType::SERVER_INFO, Some(info)) => ServerInfo::from_json(&info),
+				(_, Some(info)) => Err(DataError::MalformedPacket(info)),
+				(_, None) => Err(DataError::NoDataReceived),
+			};
+			info.map_err(|e| QueryError::from(ConnectionError::Data(e)))
+		}))
+	}
+	
+	/// The database used for tables that are not qualified with `r::db`
+	pub fn default_db(&self) -> String {
+		self.db.read().unwrap().clone()
+	}
+	
+	/// Changes the database used for tables that are not qualified with `r::db`, for this
+	/// connection and all of its clones. Queries that have already been sent are not affected.
+	pub fn use_db(&self, db: String) {
+		*self.db.write().unwrap() = db;
+	}
+	
+	/// Allocates a token and starts routing its responses to the returned receiver
+	fn register(&self) -> (u64, mpsc::UnboundedReceiver<Result<json::Json, DataError>>) {
+		let token = self.next_token.fetch_add(1, Ordering::SeqCst) as u64;
+		let (tx, rx) = mpsc::unbounded();
+		
+		//if the socket has closed the sender is dropped, so the receiver ends immediately
+		if let Some(ref mut routes) = *self.routes.lock().unwrap() {
+			routes.insert(token, tx);
+		}
+		(token, rx)
+	}
+	
+	fn release(&self, token: u64) {
+		if let Some(ref mut routes) = *self.routes.lock().unwrap() {
+			routes.remove(&token);
+		}
+	}
+	
+	fn send(&self, token: u64, query: &json::Json) -> Result<(), ConnectionError> {
+		self.writer.unbounded_send(encode_frame(token, query)).map_err(|_| closed())
+	}
+	
+	/// Sends a serialised query, resolving to a cursor once the first response arrives.
+	/// `Queryable::run_async` builds a START query from a term.
+	pub fn start(&self, query: json::Json) -> RunFuture {
+		let (token, responses) = self.register();
+		
+		let cursor = Cursor {
+			conn: self.clone(),
+			token: token,
+			responses: responses,
+			response_type: ResponseType::SUCCESS_PARTIAL,
+			buffer: VecDeque::new(),
+			notes: Vec::new(),
+			profile: None,
+			waiting: true,
+		};
+		
+		if let Err(e) = self.send(token, &query) {
+			return Box::new(future::err(QueryError::from(e)));
+		}
+		
+		Box::new(FirstBatch {cursor: Some(cursor)})
+	}
+}
+
+//cursor
+
+/// Resolves to the cursor once the response to START has arrived, so that errors in the
+/// query are reported before any results are consumed
+struct FirstBatch {
+	cursor: Option<Cursor>,
+}
+
+impl Future for FirstBatch {
+	type Item = Cursor;
+	type Error = QueryError;
+	
+	fn poll(&mut self) -> Poll<Cursor, QueryError> {
+		match try!(self.cursor.as_mut().expect("polled after completion").poll_batch()) {
+			Async::Ready(()) => Ok(Async::Ready(self.cursor.take().unwrap())),
+			Async::NotReady => Ok(Async::NotReady),
+		}
+	}
+}
+
+/// The results of a query as a `Stream`.
+///
+/// Batches are fetched with CONTINUE as the stream is polled, so a changefeed is an endless
+/// stream. If the cursor is dropped before the server has sent the final batch, a STOP is sent.
+pub struct Cursor {
+	conn: Connection,
+	token: u64,
+	responses: mpsc::UnboundedReceiver<Result<json::Json, DataError>>,
+	response_type: ResponseType,
+	buffer: VecDeque<json::Json>,
+	notes: Vec<ResponseNote>,
+	profile: Option<json::Json>,
+	/// True while a response to START or CONTINUE is outstanding
+	waiting: bool,
+}
+
+impl Cursor {
+	/// The type of the most recently received response
+	pub fn response_type(&self) -> ResponseType {
+		self.response_type
+	}
+	
+	/// True if the server has more batches available for this query
+	pub fn is_partial(&self) -> bool {
+		self.response_type == ResponseType::SUCCESS_PARTIAL
+	}
+	
+	pub fn notes(&self) -> &[ResponseNote] {
+		&self.notes
+	}
+	
+	pub fn profile(&self) -> Option<&json::Json> {
+		self.profile.as_ref()
+	}
+	
+	/// Waits for the outstanding response and buffers its data
+	fn poll_batch(&mut self) -> Poll<(), QueryError> {
+		let frame = match self.responses.poll() {
+			Ok(Async::Ready(Some(frame))) => frame,
+			Ok(Async::Ready(None)) | Err(()) => {
+				self.waiting = false;
+				self.response_type = ResponseType::SUCCESS_SEQUENCE;
+				return Err(QueryError::from(closed()));
+			},
+			Ok(Async::NotReady) => return Ok(Async::NotReady),
+		};
+		
+		self.waiting = false;
+		
+		match frame.map_err(|e| QueryError::from(ConnectionError::Data(e))).and_then(Response::from_json) {
+			Ok(response) => {
+				self.response_type = response.response_type;
+				self.buffer.extend(response.data);
+				self.notes = response.notes;
+				if response.profile.is_some() {
+					self.profile = response.profile;
+				}
+				Ok(Async::Ready(()))
+			},
+			Err(e) => {
+				//the server discards the query state after an error
+				self.response_type = ResponseType::SUCCESS_SEQUENCE;
+				Err(e)
+			},
+		}
+	}
+}
+
+impl Stream for Cursor {
+	type Item = json::Json;
+	type Error = QueryError;
+	
+	fn poll(&mut self) -> Poll<Option<json::Json>, QueryError> {
+		loop {
+			if let Some(datum) = self.buffer.pop_front() {
+				return Ok(Async::Ready(Some(datum)));
+			}
+			
+			if !self.waiting {
+				if !self.is_partial() {
+					return Ok(Async::Ready(None));
+				}
+				
+				let continue_query = json::Json::Array(vec![
+					(QueryType::CONTINUE as u32).to_json(),
+				]);
+				try!(self.conn.send(self.token, &continue_query));
+				self.waiting = true;
+			}
+			
+			match try!(self.poll_batch()) {
+				Async::Ready(()) => continue,
+				Async::NotReady => return Ok(Async::NotReady),
+			}
+		}
+	}
+}
+
+impl Drop for Cursor {
+	fn drop(&mut self) {
+		if self.is_partial() {
+			let stop_query = json::Json::Array(vec![
+				(QueryType::STOP as u32).to_json(),
+			]);
+			let _ = self.conn.send(self.token, &stop_query);
+		}
+		
+		self.conn.release(self.token);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::{Future, Stream};
+	use tokio::net::TcpStream;
+	use tokio::runtime::Runtime;
+	use byteorder::{ByteOrder, BigEndian, LittleEndian};
+	use rustc_serialize::json::Json;
+	use std::io::{BufRead, BufReader, Read, Write};
+	use std::net::TcpListener;
+	use std::path::PathBuf;
+	use std::thread;
+	use std::time::Duration;
+	use err::{AuthError, ConnectionError, QueryError};
+	use net::{connection, ConnectionBuilder, TlsOptions, HANDSHAKE_MAGIC, FRAME_HEADER_LEN};
+	use {table, Queryable};
+	use super::Connection;
+	
+	const SERVER_REPLY: &'static str = "{\"success\":true,\"min_protocol_version\":0,\"max_protocol_version\":0,\"server_version\":\"2.4.1\"}";
+	
+	#[test]
+	fn test_run_async() {
+		let mut runtime = Runtime::new().unwrap();
+		
+		let query = connection()
+			.dbname(String::from("task_planner"))
+			.connect_async()
+			.map_err(QueryError::from)
+			.and_then(|conn| table("tasks").run_async(&conn))
+			.and_then(|cursor| cursor.collect());
+		
+		runtime.block_on(query).unwrap();
+	}
+	
+	#[test]
+	fn test_handshake_framing() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let port = listener.local_addr().unwrap().port();
+		
+		//a server that reads the magic number and the first SCRAM message, then rejects the user
+		let server = thread::spawn(move || {
+			let (stream, _) = listener.accept().unwrap();
+			let mut br = BufReader::new(stream);
+			
+			let mut magic = [0u8; 4];
+			br.read_exact(&mut magic).unwrap();
+			assert_eq!(magic, HANDSHAKE_MAGIC);
+			br.get_mut().write_all(format!("{}\0", SERVER_REPLY).as_bytes()).unwrap();
+			
+			let mut packet = Vec::new();
+			br.read_until(0x00, &mut packet).unwrap();
+			packet.pop();
+			br.get_mut().write_all(b"{\"success\":false,\"error\":\"Unknown user\",\"error_code\":17}\0").unwrap();
+			Json::from_str(&String::from_utf8(packet).unwrap()).unwrap()
+		});
+		
+		let mut runtime = Runtime::new().unwrap();
+		let conn = connection()
+			.hostname(String::from("127.0.0.1"))
+			.port(port)
+			.user(String::from("ann"), String::from("secret"))
+			.connect_async();
+		
+		match runtime.block_on(conn) {
+			Err(ConnectionError::Auth(AuthError::ReqlAuthError(17, _))) => (),
+			Err(e) => panic!("expected the server to reject the user, got {:?}", e),
+			Ok(_) => panic!("expected the handshake to fail"),
+		}
+		
+		let packet = server.join().unwrap();
+		assert_eq!(packet.find("protocol_version"), Some(&Json::U64(0)));
+		assert_eq!(packet.find("authentication_method").and_then(Json::as_string), Some("SCRAM-SHA-256"));
+		assert!(packet.find("authentication").and_then(Json::as_string).unwrap().starts_with("n,,n=ann,r="));
+	}
+	
+	#[test]
+	fn test_query_framing() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = listener.local_addr().unwrap();
+		
+		//a server that answers one query with a complete sequence
+		let server = thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			
+			let mut header = [0u8; FRAME_HEADER_LEN];
+			stream.read_exact(&mut header).unwrap();
+			let mut body = vec![0u8; LittleEndian::read_u32(&header[8..12]) as usize];
+			stream.read_exact(&mut body).unwrap();
+			
+			let reply = b"{\"t\":2,\"r\":[1,2]}";
+			LittleEndian::write_u32(&mut header[8..12], reply.len() as u32);
+			stream.write_all(&header).unwrap();
+			stream.write_all(reply).unwrap();
+			(BigEndian::read_u64(&header[0..8]), String::from_utf8(body).unwrap())
+		});
+		
+		let mut runtime = Runtime::new().unwrap();
+		let version = ConnectionBuilder::validate_server_reply(&Json::from_str(SERVER_REPLY).unwrap()).unwrap();
+		let conn = runtime.block_on(TcpStream::connect(&addr).map(move |stream| Connection::new(stream, version, String::from("test")))).unwrap();
+		
+		//every clone sees the database selected through any of them
+		conn.clone().use_db(String::from("blog"));
+		assert_eq!(conn.default_db(), "blog");
+		
+		let rows = runtime.block_on(table("tasks").run_async(&conn).and_then(|cursor| cursor.collect())).unwrap();
+		assert_eq!(rows, vec![Json::U64(1), Json::U64(2)]);
+		
+		let (token, query) = server.join().unwrap();
+		assert_eq!(token, 0);
+		assert_eq!(query, "[1,[15,[\"tasks\"]],{\"db\":[14,[\"blog\"]]}]");
+	}
+	
+	#[test]
+	fn test_unsupported_options() {
+		//these fail before anything is spawned, so no runtime is needed
+		let conn = connection().tls(TlsOptions::new(PathBuf::from("ca.pem"))).connect_async();
+		assert!(conn.wait().is_err());
+		
+		let conn = connection().read_timeout(Some(Duration::from_secs(5))).connect_async();
+		assert!(conn.wait().is_err());
+		
+		let conn = connection().write_timeout(Some(Duration::from_secs(5))).connect_async();
+		assert!(conn.wait().is_err());
+	}
+}
@@ -1,80 +1,614 @@
 use std::io::prelude::*;
-use std::net::TcpStream;
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
 use std::io;
+use std::time::{Duration, Instant};
 use std::str;
 use std::io::BufReader;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock, Condvar};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use rustc_serialize::json;
 use byteorder::{LittleEndian, BigEndian, WriteBytesExt, ReadBytesExt};
-use err::{ConnectionError, DataError};
+use err::{ConnectionError, DataError, QueryError};
+use ql2::{QueryType, ResponseType};
+use Response;
 
 mod scram;
+mod tls;
+mod pool;
+mod reconnect;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+
+pub use self::tls::{TlsOptions, TlsVersion};
+pub use self::pool::{Pool, PoolBuilder, PooledConnection};
+pub use self::reconnect::{Reconnecting, ReconnectBuilder};
 
 const PROTOCOL_VERSION: u64 = 0;
 
+//transport
+
+/// A byte stream that a connection can be established over.
+///
+/// Once the handshake is complete the transport is split so that responses can be read on a
+/// background thread while queries are written from any thread using the connection.
+pub trait Transport : Read + Write + Send + 'static {
+	type Reader: Read + Send + 'static;
+	type Writer: Write + Send + 'static;
+	
+	fn set_read_timeout(&self, val: Option<Duration>) -> io::Result<()>;
+	fn set_write_timeout(&self, val: Option<Duration>) -> io::Result<()>;
+	
+	/// Splits the transport into a half that is only read from and a half that is only written to
+	fn split(self) -> io::Result<(Self::Reader, Self::Writer)>;
+	
+	fn set_writer_timeout(writer: &Self::Writer, val: Option<Duration>) -> io::Result<()>;
+	
+	/// Closes the transport, which must cause any blocked read on the reader to return
+	fn shutdown(writer: &Self::Writer) -> io::Result<()>;
+}
+
+impl Transport for TcpStream {
+	type Reader = TcpStream;
+	type Writer = TcpStream;
+	
+	fn set_read_timeout(&self, val: Option<Duration>) -> io::Result<()> {
+		TcpStream::set_read_timeout(self, val)
+	}
+	
+	fn set_write_timeout(&self, val: Option<Duration>) -> io::Result<()> {
+		TcpStream::set_write_timeout(self, val)
+	}
+	
+	fn split(self) -> io::Result<(TcpStream, TcpStream)> {
+		try!(TcpStream::set_read_timeout(&self, None));
+		let reader = try!(self.try_clone());
+		Ok((reader, self))
+	}
+	
+	fn set_writer_timeout(writer: &TcpStream, val: Option<Duration>) -> io::Result<()> {
+		writer.set_write_timeout(val)
+	}
+	
+	fn shutdown(writer: &TcpStream) -> io::Result<()> {
+		writer.shutdown(Shutdown::Both)
+	}
+}
+
+/// The transport used by `ConnectionBuilder::connect`, depending on whether TLS was requested
+pub enum Socket {
+	Plain(TcpStream),
+	Tls(tls::TlsStream),
+}
+
+impl Read for Socket {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match *self {
+			Socket::Plain(ref mut s) => s.read(buf),
+			Socket::Tls(ref mut s) => s.read(buf),
+		}
+	}
+}
+
+impl Write for Socket {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match *self {
+			Socket::Plain(ref mut s) => s.write(buf),
+			Socket::Tls(ref mut s) => s.write(buf),
+		}
+	}
+	
+	fn flush(&mut self) -> io::Result<()> {
+		match *self {
+			Socket::Plain(ref mut s) => s.flush(),
+			Socket::Tls(ref mut s) => s.flush(),
+		}
+	}
+}
+
+/// Either side of a split `Socket`
+pub enum SocketHalf {
+	Plain(TcpStream),
+	Tls(tls::TlsHalf),
+}
+
+impl Read for SocketHalf {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match *self {
+			SocketHalf::Plain(ref mut s) => s.read(buf),
+			SocketHalf::Tls(ref mut s) => s.read(buf),
+		}
+	}
+}
+
+impl Write for SocketHalf {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match *self {
+			SocketHalf::Plain(ref mut s) => s.write(buf),
+			SocketHalf::Tls(ref mut s) => s.write(buf),
+		}
+	}
+	
+	fn flush(&mut self) -> io::Result<()> {
+		match *self {
+			SocketHalf::Plain(ref mut s) => s.flush(),
+			SocketHalf::Tls(ref mut s) => s.flush(),
+		}
+	}
+}
+
+impl Transport for Socket {
+	type Reader = SocketHalf;
+	type Writer = SocketHalf;
+	
+	fn set_read_timeout(&self, val: Option<Duration>) -> io::Result<()> {
+		match *self {
+			Socket::Plain(ref s) => Transport::set_read_timeout(s, val),
+			Socket::Tls(ref s) => Transport::set_read_timeout(s, val),
+		}
+	}
+	
+	fn set_write_timeout(&self, val: Option<Duration>) -> io::Result<()> {
+		match *self {
+			Socket::Plain(ref s) => Transport::set_write_timeout(s, val),
+			Socket::Tls(ref s) => Transport::set_write_timeout(s, val),
+		}
+	}
+	
+	fn split(self) -> io::Result<(SocketHalf, SocketHalf)> {
+		match self {
+			Socket::Plain(s) => {
+				let (reader, writer) = try!(s.split());
+				Ok((SocketHalf::Plain(reader), SocketHalf::Plain(writer)))
+			},
+			Socket::Tls(s) => {
+				let (reader, writer) = try!(s.split());
+				Ok((SocketHalf::Tls(reader), SocketHalf::Tls(writer)))
+			},
+		}
+	}
+	
+	fn set_writer_timeout(writer: &SocketHalf, val: Option<Duration>) -> io::Result<()> {
+		match *writer {
+			SocketHalf::Plain(ref s) => s.set_write_timeout(val),
+			SocketHalf::Tls(ref s) => s.set_write_timeout(val),
+		}
+	}
+	
+	fn shutdown(writer: &SocketHalf) -> io::Result<()> {
+		match *writer {
+			SocketHalf::Plain(ref s) => s.shutdown(Shutdown::Both),
+			SocketHalf::Tls(ref s) => s.shutdown(),
+		}
+	}
+}
+
+//wire format
+
+const HANDSHAKE_MAGIC: [u8; 4] = [0xc3, 0xbd, 0xc2, 0x34];
+
+/// The length of the token and length fields that precede each query and response
+const FRAME_HEADER_LEN: usize = 12;
+
+/// Serialises a query as a token, a length and the JSON body
+fn encode_frame(token: u64, query: &json::Json) -> Vec<u8> {
+	let serialised_query = format!("{}", query);
+	let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + serialised_query.len());
+	frame.write_u64::<BigEndian>(token).unwrap();
+	frame.write_u32::<LittleEndian>(serialised_query.len() as u32).unwrap();
+	frame.extend_from_slice(serialised_query.as_bytes());
+	frame
+}
+
+/// Parses the body of a response frame
+fn decode_frame(buf: &[u8]) -> Result<json::Json, DataError> {
+	match str::from_utf8(buf) {
+		Ok(msg) => json::Json::from_str(msg).map_err(|_| DataError::InvalidJson(msg.to_owned())),
+		Err(_) => Err(DataError::InvalidUtf8),
+	}
+}
+
+/// Serialises a handshake message, which is null terminated rather than length prefixed
+fn encode_packet(packet: &json::Json) -> Vec<u8> {
+	let mut buf = format!("{}", packet).into_bytes();
+	buf.push(0x00);
+	buf
+}
+
+/// Parses a handshake message read up to and including its null terminator
+fn decode_packet(buf: &[u8]) -> Result<json::Json, ConnectionError> {
+	if buf.last() != Some(&0x00) {
+		return Err(ConnectionError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "No data received")));
+	}
+	
+	let ret_msg = try!(str::from_utf8(&buf[0..buf.len()-1]).map_err(|_| DataError::InvalidUtf8));
+	json::Json::from_str(ret_msg).map_err(|_| ConnectionError::Data(DataError::InvalidJson(ret_msg.to_owned())))
+}
+
+//response routing
+
+/// Responses received by the reader thread, waiting to be collected by token
+struct Router {
+	state: Mutex<RouterState>,
+	cond: Condvar,
+}
+
+struct RouterState {
+	/// Tokens with a query in flight, and the responses received for them so far
+	pending: HashMap<u64, VecDeque<Result<json::Json, DataError>>>,
+	/// Set once the reader has stopped, after which no more responses will arrive
+	closed: Option<(io::ErrorKind, String)>,
+}
+
+fn read_frame<R: BufRead>(br: &mut R) -> io::Result<(u64, Result<json::Json, DataError>)> {
+	let token = try!(br.read_u64::<BigEndian>());
+	let length = try!(br.read_u32::<LittleEndian>()) as usize;
+	let mut buf = Vec::new();
+	buf.resize(length, 0);
+	try!(br.read_exact(buf.as_mut_slice()));
+	Ok((token, decode_frame(&buf)))
+}
+
+/// Reads responses until the transport fails or is shut down, handing each to the query
+/// waiting on its token. Responses for tokens nobody is waiting on are discarded.
+fn read_loop<R: Read>(reader: R, router: Arc<Router>) {
+	let mut br = BufReader::new(reader);
+	
+	loop {
+		match read_frame(&mut br) {
+			Ok((token, frame)) => {
+				let mut state = router.state.lock().unwrap();
+				if let Some(queue) = state.pending.get_mut(&token) {
+					queue.push_back(frame);
+					router.cond.notify_all();
+				}
+			},
+			Err(e) => {
+				let mut state = router.state.lock().unwrap();
+				state.closed = Some((e.kind(), e.to_string()));
+				router.cond.notify_all();
+				return;
+			},
+		}
+	}
+}
+
+//server information
+
+/// The server's description of itself from the first message of the handshake
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ServerVersion {
+	/// The RethinkDB release, e.g. `2.4.1~0bionic`
+	pub version: String,
+	pub min_protocol_version: u64,
+	pub max_protocol_version: u64,
+}
+
+impl ServerVersion {
+	/// Parses the handshake reply, returning `None` if it does not report success
+	fn from_handshake(obj: &json::Json) -> Option<ServerVersion> {
+		match obj.find("success") {
+			Some(&json::Json::Boolean(true)) => (),
+			_ => return None
+		};
+		
+		let version = obj.find("server_version").and_then(|v| v.as_string());
+		let min_protocol_version = obj.find("min_protocol_version").and_then(|v| v.as_u64());
+		let max_protocol_version = obj.find("max_protocol_version").and_then(|v| v.as_u64());
+		
+		match (version, min_protocol_version, max_protocol_version) {
+			(Some(version), Some(min), Some(max)) => Some(ServerVersion {
+				version: version.to_owned(),
+				min_protocol_version: min,
+				max_protocol_version: max,
+			}),
+			_ => None,
+		}
+	}
+	
+	/// True if the server accepts the given version of the wire protocol
+	pub fn supports_protocol(&self, version: u64) -> bool {
+		self.min_protocol_version <= version && version <= self.max_protocol_version
+	}
+}
+
+/// The cluster node a connection is attached to, as returned by `Connection::server`
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ServerInfo {
+	pub id: String,
+	/// The server's name, which is `None` for a proxy
+	pub name: Option<String>,
+	/// True if the node is a proxy rather than a server storing data
+	pub proxy: bool,
+}
+
+impl ServerInfo {
+	pub fn from_json(obj: &json::Json) -> Result<ServerInfo, DataError> {
+		let malformed = || DataError::MalformedPacket(obj.clone());
+		
+		let id = try!(obj.find("id").and_then(|v| v.as_string()).ok_or_else(&malformed));
+		
+		let name = match obj.find("name") {
+			Some(&json::Json::String(ref s)) => Some(s.clone()),
+			Some(&json::Json::Null) | None => None,
+			_ => return Err(malformed()),
+		};
+		
+		let proxy = match obj.find("proxy") {
+			Some(&json::Json::Boolean(b)) => b,
+			None => false,
+			_ => return Err(malformed()),
+		};
+		
+		Ok(ServerInfo {id: id.to_owned(), name: name, proxy: proxy})
+	}
+}
+
 //connection
-pub struct Connection {
-	br: BufReader<TcpStream>,
-	next_token: u64,
+
+/// An authenticated connection to the server.
+///
+/// Any number of queries can be in flight at once: each is sent with its own token, and a
+/// background thread routes responses back to the query they belong to. A `Connection` can
+/// therefore be shared between threads, and several cursors can be open on it at once.
+pub struct Connection<S: Transport = Socket> {
+	writer: Mutex<S::Writer>,
+	router: Arc<Router>,
+	next_token: AtomicUsize,
+	db: RwLock<String>,
+	read_timeout: RwLock<Option<Duration>>,
+	write_timeout: RwLock<Option<Duration>>,
+	server_version: ServerVersion,
 }
 
-impl Connection {
-	fn get_next_token(&mut self) -> u64 {
-		let t = self.next_token;
-		self.next_token = self.next_token + 1;
-		t
+impl<S: Transport> Connection<S> {
+	/// The server release and protocol versions reported during the handshake
+	pub fn server_version(&self) -> &ServerVersion {
+		&self.server_version
 	}
 	
-	pub fn send_query(&mut self, query: &json::Json) -> io::Result<u64> {
-		let serialised_query = format!("{}", query);
+	/// Asks the server which cluster node this connection is attached to
+	pub fn server(&self) -> Result<ServerInfo, QueryError> {
+		let info = try!(self.server_info_raw());
+		ServerInfo::from_json(&info).map_err(|e| QueryError::from(ConnectionError::Data(e)))
+	}
+	
+	/// False once the transport has failed or been closed by the server, after which every query
+	/// on this connection fails
+	pub fn is_open(&self) -> bool {
+		self.router.state.lock().unwrap().closed.is_none()
+	}
+	
+	/// The database used for tables that are not qualified with `r::db`
+	pub fn default_db(&self) -> String {
+		self.db.read().unwrap().clone()
+	}
+	
+	/// Changes the database used for tables that are not qualified with `r::db`. Queries that
+	/// have already been sent are not affected.
+	pub fn use_db(&self, db: String) {
+		*self.db.write().unwrap() = db;
+	}
+	
+	/// Sets how long to wait for each response before failing with `ConnectionError::Timeout`.
+	/// The query keeps running on the server, and its response is discarded if it arrives later.
+	pub fn set_read_timeout(&self, val: Option<Duration>) {
+		*self.read_timeout.write().unwrap() = val;
+	}
+	
+	/// Sets how long to wait while sending a query before failing with `ConnectionError::Timeout`.
+	/// It applies from the next query sent.
+	pub fn set_write_timeout(&self, val: Option<Duration>) {
+		*self.write_timeout.write().unwrap() = val;
+	}
+	
+	fn get_next_token(&self) -> u64 {
+		self.next_token.fetch_add(1, Ordering::SeqCst) as u64
+	}
+	
+	/// Sends a query on a new token and returns the token to pass to `recv_response`
+	pub fn send_query(&self, query: &json::Json) -> Result<u64, ConnectionError> {
 		let token = self.get_next_token();
-		let length = serialised_query.len() as u32;
-		
-		try!(self.br.get_mut().write_u64::<BigEndian>(token));
-		try!(self.br.get_mut().write_u32::<LittleEndian>(length));
-		try!(self.br.get_mut().write_all(serialised_query.as_bytes()));
+		try!(self.send_query_with_token(token, query));
 		Ok(token)
 	}
 	
-	pub fn recv_response(&mut self) -> Result<json::Json, ConnectionError> {
-		let token = try!(self.br.read_u64::<BigEndian>());
-		let length = try!(self.br.read_u32::<LittleEndian>()) as usize;
-		let mut buf = Vec::new();
-		buf.resize(length, 0);
-		try!(self.br.read_exact(buf.as_mut_slice()));
-		let ret_msg = try!(str::from_utf8(&buf).map_err(|_| ConnectionError::Data(DataError::InvalidUtf8)));
-		let ret_json = try!(json::Json::from_str(ret_msg).map_err(|_| ConnectionError::Data(DataError::InvalidJson(ret_msg.to_owned()))));
-		Ok(ret_json)
+	/// Sends a query on a new token without waiting for a response, for queries run with the
+	/// `noreply` optarg. Anything the server sends back on the token is discarded.
+	pub fn send_noreply(&self, query: &json::Json) -> Result<(), ConnectionError> {
+		let token = self.get_next_token();
+		self.write_frame(token, query)
+	}
+	
+	/// Sends a query using an existing token, as required for CONTINUE and STOP
+	pub fn send_query_with_token(&self, token: u64, query: &json::Json) -> Result<(), ConnectionError> {
+		//registered before sending, so the response cannot arrive before anyone is waiting for it
+		self.router.state.lock().unwrap().pending.entry(token).or_insert_with(VecDeque::new);
+		
+		let result = self.write_frame(token, query);
+		if result.is_err() {
+			self.release_token(token);
+		}
+		result
+	}
+	
+	fn write_frame(&self, token: u64, query: &json::Json) -> Result<(), ConnectionError> {
+		//the whole frame is written under one lock so frames from different threads cannot interleave
+		let frame = encode_frame(token, query);
+		let mut writer = self.writer.lock().unwrap();
+		try!(S::set_writer_timeout(&writer, *self.write_timeout.read().unwrap()));
+		try!(writer.write_all(&frame).map_err(timed_io_error));
+		writer.flush().map_err(timed_io_error)
+	}
+	
+	/// Stops routing responses for a token once its query is finished with. Responses that
+	/// arrive for it afterwards are discarded.
+	pub fn release_token(&self, token: u64) {
+		self.router.state.lock().unwrap().pending.remove(&token);
+	}
+	
+	/// Sends a SERVER_INFO query and returns the server's description of itself
+	fn server_info_raw(&self) -> Result<json::Json, QueryError> {
+		self.server_info_until(self.read_deadline())
+	}
+	
+	/// Like `server_info_raw`, but gives up at `deadline` rather than after the read timeout
+	fn server_info_until(&self, deadline: Option<Instant>) -> Result<json::Json, QueryError> {
+		let query = json::Json::Array(vec![
+			json::Json::U64(QueryType::SERVER_INFO as u64),
+		]);
+		
+		let token = try!(self.send_query(&query));
+		let reply = self.recv_response_until(token, deadline);
+		self.release_token(token);
+		let reply = try!(reply);
+		let response = try!(Response::from_json(reply.clone()));
+		
+		match (response.response_type(), response.into_data().pop()) {
+			(ResponseType::SERVER_INFO, Some(info)) => Ok(info),
+			_ => Err(QueryError::from(ConnectionError::Data(DataError::MalformedPacket(reply)))),
+		}
+	}
+	
+	/// Blocks until every query previously run on this connection with `noreply` has completed
+	pub fn noreply_wait(&self) -> Result<(), QueryError> {
+		let query = json::Json::Array(vec![
+			json::Json::U64(QueryType::NOREPLY_WAIT as u64),
+		]);
+		
+		let token = try!(self.send_query(&query));
+		let reply = self.recv_response(token);
+		self.release_token(token);
+		let reply = try!(reply);
+		
+		match try!(Response::from_json(reply.clone())).response_type() {
+			ResponseType::WAIT_COMPLETE => Ok(()),
+			_ => Err(QueryError::from(ConnectionError::Data(DataError::MalformedPacket(reply)))),
+		}
+	}
+	
+	/// Waits for the next response to the query sent on `token`
+	pub fn recv_response(&self, token: u64) -> Result<json::Json, ConnectionError> {
+		self.recv_response_until(token, self.read_deadline())
+	}
+	
+	fn read_deadline(&self) -> Option<Instant> {
+		self.read_timeout.read().unwrap().map(|timeout| Instant::now() + timeout)
+	}
+	
+	fn recv_response_until(&self, token: u64, deadline: Option<Instant>) -> Result<json::Json, ConnectionError> {
+		let mut state = self.router.state.lock().unwrap();
+		
+		loop {
+			match state.pending.get_mut(&token) {
+				Some(queue) => if let Some(frame) = queue.pop_front() {
+					return frame.map_err(ConnectionError::Data);
+				},
+				None => return Err(ConnectionError::Io(io::Error::new(io::ErrorKind::InvalidInput, "No query is in flight on this token"))),
+			}
+			
+			if let Some((kind, ref msg)) = state.closed {
+				return Err(ConnectionError::Io(io::Error::new(kind, msg.clone())));
+			}
+			
+			state = match deadline {
+				Some(deadline) => {
+					let remaining = try!(time_remaining(deadline));
+					self.router.cond.wait_timeout(state, remaining).unwrap().0
+				},
+				None => self.router.cond.wait(state).unwrap(),
+			};
+		}
 	}
 }
 
-struct AuthConnection {
-	br: BufReader<TcpStream>
+impl<S: Transport> Drop for Connection<S> {
+	fn drop(&mut self) {
+		//stops the reader thread
+		let _ = S::shutdown(&self.writer.lock().unwrap());
+	}
 }
 
-impl AuthConnection {
-	fn send_packet(&mut self, packet: &json::Json) -> io::Result<()> {
-		try!(write!(self.br.get_mut(),"{}", packet));
-		self.br.get_mut().write_all(&[0x00])
+fn time_remaining(deadline: Instant) -> Result<Duration, ConnectionError> {
+	let now = Instant::now();
+	
+	if now >= deadline {
+		Err(ConnectionError::Timeout)
+	} else {
+		Ok(deadline - now)
+	}
+}
+
+/// Converts the error from a socket operation that has a timeout set. Socket timeouts are
+/// reported as WouldBlock on unix and TimedOut on windows.
+fn timed_io_error(err: io::Error) -> ConnectionError {
+	match err.kind() {
+		io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ConnectionError::Timeout,
+		_ => ConnectionError::Io(err),
+	}
+}
+
+struct AuthConnection<S: Transport> {
+	br: BufReader<S>,
+	deadline: Instant,
+}
+
+impl<S: Transport> AuthConnection<S> {
+	/// Limits the next socket operation to the time remaining before the handshake deadline
+	fn arm_timeout(&mut self) -> Result<(), ConnectionError> {
+		let remaining = try!(time_remaining(self.deadline));
+		try!(self.br.get_ref().set_read_timeout(Some(remaining)));
+		try!(self.br.get_ref().set_write_timeout(Some(remaining)));
+		Ok(())
+	}
+	
+	fn send_magic(&mut self) -> Result<(), ConnectionError> {
+		try!(self.arm_timeout());
+		try!(self.br.get_mut().write_all(&HANDSHAKE_MAGIC).map_err(timed_io_error));
+		Ok(())
+	}
+	
+	fn send_packet(&mut self, packet: &json::Json) -> Result<(), ConnectionError> {
+		try!(self.arm_timeout());
+		try!(self.br.get_mut().write_all(&encode_packet(packet)).map_err(timed_io_error));
+		Ok(())
 	}
 	
 	fn recv_packet(&mut self) -> Result<json::Json, ConnectionError> {
 		let mut buffer = Vec::new();
 		
-		let bytes_read = try!(self.br.read_until(0x00, &mut buffer));
-		
-		if bytes_read == 0 {
-			return Err(ConnectionError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "No data received")));
-		}
-		
-		let ret_msg = try!(str::from_utf8(&buffer[0..bytes_read-1]).map_err(|_| DataError::InvalidUtf8));
-		json::Json::from_str(ret_msg).map_err(|_| ConnectionError::Data(DataError::InvalidJson(ret_msg.to_owned())))
+		try!(self.arm_timeout());
+		try!(self.br.read_until(0x00, &mut buffer).map_err(timed_io_error));
+		decode_packet(&buffer)
 	}
 	
-	fn into_connection(self) -> Connection {
-		Connection {br: self.br, next_token: 0}
+	/// Splits the transport and starts the reader thread. The server sends nothing between the
+	/// end of the handshake and the first query, so the read buffer is empty at this point.
+	fn into_connection(self, server_version: ServerVersion, db: String, read_timeout: Option<Duration>, write_timeout: Option<Duration>) -> Result<Connection<S>, ConnectionError> {
+		let (reader, writer) = try!(self.br.into_inner().split());
+		
+		let router = Arc::new(Router {
+			state: Mutex::new(RouterState {pending: HashMap::new(), closed: None}),
+			cond: Condvar::new(),
+		});
+		
+		let reader_router = router.clone();
+		try!(thread::Builder::new().name(String::from("recogitate-reader")).spawn(move || read_loop(reader, reader_router)));
+		
+		Ok(Connection {
+			writer: Mutex::new(writer),
+			router: router,
+			next_token: AtomicUsize::new(0),
+			db: RwLock::new(db),
+			read_timeout: RwLock::new(read_timeout),
+			write_timeout: RwLock::new(write_timeout),
+			server_version: server_version,
+		})
 	}
 }
 
+#[derive(Clone)]
 pub struct ConnectionBuilder {
 	hostname: String,
 	port: u16,
@@ -82,6 +616,9 @@ pub struct ConnectionBuilder {
 	user: String,
 	pass: String,
 	timeout: u32,
+	read_timeout: Option<Duration>,
+	write_timeout: Option<Duration>,
+	tls: Option<TlsOptions>,
 }
 
 impl ConnectionBuilder {
@@ -106,39 +643,103 @@ impl ConnectionBuilder {
 		self
 	}
 	
+	/// Sets the time limit, in seconds, for establishing the connection and completing the handshake
 	pub fn timeout(mut self, val: u32) -> ConnectionBuilder {
 		self.timeout = val;
 		self
 	}
 	
-	fn validate_server_reply(obj: &json::Json) -> bool {
-		match obj.find("success") {
-			Some(&json::Json::Boolean(true)) => (),
-			_ => return false
-		};
-		match obj.find("min_protocol_version") {
-			Some(&json::Json::U64(x)) => return x <= PROTOCOL_VERSION,
-			_ => return false
-		};
+	/// Sets the time limit for receiving each response once connected; see `Connection::set_read_timeout`
+	pub fn read_timeout(mut self, val: Option<Duration>) -> ConnectionBuilder {
+		self.read_timeout = val;
+		self
+	}
+	
+	/// Sets the time limit for sending each query once connected
+	pub fn write_timeout(mut self, val: Option<Duration>) -> ConnectionBuilder {
+		self.write_timeout = val;
+		self
+	}
+	
+	/// Connects over TLS instead of plain TCP
+	pub fn tls(mut self, val: TlsOptions) -> ConnectionBuilder {
+		self.tls = Some(val);
+		self
+	}
+	
+	/// Checks that the server accepted the magic number and supports our protocol version
+	fn validate_server_reply(obj: &json::Json) -> Result<ServerVersion, ConnectionError> {
+		match ServerVersion::from_handshake(obj) {
+			Some(ref version) if version.supports_protocol(PROTOCOL_VERSION) => Ok(version.clone()),
+			_ => Err(ConnectionError::Data(DataError::MalformedPacket(obj.clone()))),
+		}
+	}
+	
+	/// Tries each address the hostname resolves to until one accepts the connection
+	fn connect_tcp(&self, deadline: Instant) -> Result<TcpStream, ConnectionError> {
+		let mut last_err = None;
+		
+		for addr in try!((self.hostname.as_str(), self.port).to_socket_addrs()) {
+			match TcpStream::connect_timeout(&addr, try!(time_remaining(deadline))) {
+				Ok(stream) => return Ok(stream),
+				Err(e) => last_err = Some(e),
+			}
+		}
+		
+		Err(match last_err {
+			Some(e) => timed_io_error(e),
+			None => ConnectionError::Io(io::Error::new(io::ErrorKind::NotFound, "The hostname did not resolve to any addresses")),
+		})
+	}
+	
+	/// Creates a pool of connections that all use these settings
+	pub fn pool(self) -> PoolBuilder {
+		PoolBuilder::new(self)
+	}
+	
+	/// Creates a connection that is reopened with these settings whenever it drops
+	pub fn reconnecting(self) -> ReconnectBuilder {
+		ReconnectBuilder::new(self)
 	}
 	
 	pub fn connect(self) -> Result<Connection, ConnectionError> {
-		let mut stream = try!(TcpStream::connect((self.hostname.as_str(), self.port)));
+		let deadline = Instant::now() + Duration::from_secs(self.timeout as u64);
+		
+		let stream = try!(self.connect_tcp(deadline));
 		//try!(stream.set_nonblocking(true));
 		try!(stream.set_nodelay(true));
-		try!(stream.write_all(&[0xc3, 0xbd, 0xc2, 0x34]));
 		
-		let mut conn = AuthConnection {br: BufReader::new(stream)};
+		let socket = match self.tls {
+			Some(ref options) => {
+				let remaining = try!(time_remaining(deadline));
+				try!(stream.set_read_timeout(Some(remaining)));
+				try!(stream.set_write_timeout(Some(remaining)));
+				Socket::Tls(try!(tls::connect(options, &self.hostname, stream)))
+			},
+			None => Socket::Plain(stream),
+		};
+		
+		self.handshake(socket, deadline)
+	}
+	
+	/// Performs the RethinkDB handshake over an already established transport, ignoring the
+	/// hostname, port and TLS settings
+	pub fn connect_stream<S: Transport>(self, stream: S) -> Result<Connection<S>, ConnectionError> {
+		let deadline = Instant::now() + Duration::from_secs(self.timeout as u64);
+		self.handshake(stream, deadline)
+	}
+	
+	fn handshake<S: Transport>(self, stream: S, deadline: Instant) -> Result<Connection<S>, ConnectionError> {
+		let mut conn = AuthConnection {br: BufReader::new(stream), deadline: deadline};
+		try!(conn.send_magic());
 		
 		let obj_reply = try!(conn.recv_packet());
 		
 		//reply validation
-		if !Self::validate_server_reply(&obj_reply) {
-			return Err(ConnectionError::Data(DataError::MalformedPacket(obj_reply)));
-		}
+		let server_version = try!(Self::validate_server_reply(&obj_reply));
 		
 		//begin authentication handshake
-		let (packet, hs_a) = scram::begin_handshake(&self.user, &self.pass);
+		let (packet, hs_a) = try!(scram::begin_handshake(&self.user, &self.pass));
 		//println!("Client sends:");
 		//println!("{}", packet);
 		try!(conn.send_packet(&packet));
@@ -157,7 +758,7 @@ impl ConnectionBuilder {
 		//println!("{}", packet);
 		
 		try!(hs_b.handshake_c(&packet));
-		Ok(conn.into_connection())
+		conn.into_connection(server_version, self.dbname, self.read_timeout, self.write_timeout)
 	}
 }
 
@@ -168,16 +769,76 @@ pub fn connection() -> ConnectionBuilder {
 		dbname: String::from("test"),
 		user: String::from("admin"),
 		pass: String::new(),
-		timeout: 20
+		timeout: 20,
+		read_timeout: None,
+		write_timeout: None,
+		tls: None,
 	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use err::ConnectionError;
+	use std::net::TcpListener;
 	
 	#[test]
 	fn test_connection() {
 		connection().connect().unwrap();
 	}
+	
+	#[test]
+	fn test_server() {
+		let conn = connection().connect().unwrap();
+		assert!(!conn.server_version().version.is_empty());
+		
+		let info = conn.server().unwrap();
+		assert!(!info.id.is_empty());
+		assert_eq!(info.name.is_none(), info.proxy);
+	}
+	
+	#[test]
+	fn test_parse_server_info() {
+		let reply = json::Json::from_str("{\"success\":true,\"min_protocol_version\":0,\"max_protocol_version\":0,\"server_version\":\"2.4.1\"}").unwrap();
+		let version = ConnectionBuilder::validate_server_reply(&reply).unwrap();
+		assert_eq!(version.version, "2.4.1");
+		assert_eq!(version.max_protocol_version, 0);
+		assert!(version.supports_protocol(0));
+		assert!(!version.supports_protocol(1));
+		
+		let reply = json::Json::from_str("{\"success\":true,\"min_protocol_version\":1,\"max_protocol_version\":2,\"server_version\":\"3.0.0\"}").unwrap();
+		assert!(ConnectionBuilder::validate_server_reply(&reply).is_err());
+		
+		let info = json::Json::from_str("{\"id\":\"6d6aa5c8\",\"name\":null,\"proxy\":true}").unwrap();
+		assert_eq!(ServerInfo::from_json(&info).unwrap(), ServerInfo {id: String::from("6d6aa5c8"), name: None, proxy: true});
+	}
+	
+	#[test]
+	fn test_timed_io_error() {
+		let would_block = || io::Error::new(io::ErrorKind::WouldBlock, "would block");
+		
+		match timed_io_error(would_block()) {
+			ConnectionError::Timeout => (),
+			e => panic!("expected a timeout, got {:?}", e),
+		}
+		
+		//only operations with a timeout set report timeouts
+		match ConnectionError::from(would_block()) {
+			ConnectionError::Io(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+			e => panic!("expected an io error, got {:?}", e),
+		}
+	}
+	
+	#[test]
+	fn test_connect_timeout() {
+		//the connection is queued but never accepted, so the handshake reply never comes
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let port = listener.local_addr().unwrap().port();
+		
+		match connection().hostname(String::from("127.0.0.1")).port(port).timeout(1).connect() {
+			Err(ConnectionError::Timeout) => (),
+			Err(e) => panic!("expected a timeout, got {:?}", e),
+			Ok(_) => panic!("expected a timeout"),
+		}
+	}
 }
\ No newline at end of file
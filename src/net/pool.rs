@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::{Arc, Weak, Mutex, Condvar};
+use std::thread;
+use std::time::{Duration, Instant};
+use err::{ConnectionError, QueryError};
+use super::{Connection, ConnectionBuilder};
+
+/// Settings for a `Pool`, created with `ConnectionBuilder::pool`
+pub struct PoolBuilder {
+	builder: ConnectionBuilder,
+	min_idle: usize,
+	max_size: usize,
+	checkout_timeout: Duration,
+	max_lifetime: Option<Duration>,
+	idle_timeout: Option<Duration>,
+	health_check: bool,
+}
+
+impl PoolBuilder {
+	pub fn new(builder: ConnectionBuilder) -> PoolBuilder {
+		PoolBuilder {
+			builder: builder,
+			min_idle: 0,
+			max_size: 10,
+			checkout_timeout: Duration::from_secs(30),
+			max_lifetime: Some(Duration::from_secs(30 * 60)),
+			idle_timeout: Some(Duration::from_secs(10 * 60)),
+			health_check: true,
+		}
+	}
+	
+	/// The number of idle connections the pool tries to keep open
+	pub fn min_idle(mut self, val: usize) -> PoolBuilder {
+		self.min_idle = val;
+		self
+	}
+	
+	/// The maximum number of connections, idle or checked out
+	pub fn max_size(mut self, val: usize) -> PoolBuilder {
+		self.max_size = val;
+		self
+	}
+	
+	/// How long `Pool::get` waits for a connection to become available
+	pub fn checkout_timeout(mut self, val: Duration) -> PoolBuilder {
+		self.checkout_timeout = val;
+		self
+	}
+	
+	/// Connections older than this are closed instead of being reused
+	pub fn max_lifetime(mut self, val: Option<Duration>) -> PoolBuilder {
+		self.max_lifetime = val;
+		self
+	}
+	
+	/// Idle connections unused for this long are closed, down to `min_idle`
+	pub fn idle_timeout(mut self, val: Option<Duration>) -> PoolBuilder {
+		self.idle_timeout = val;
+		self
+	}
+	
+	/// Whether to send a SERVER_INFO query to check each connection before handing it out
+	pub fn health_check(mut self, val: bool) -> PoolBuilder {
+		self.health_check = val;
+		self
+	}
+	
+	/// Opens `min_idle` connections and starts the background reaper
+	pub fn build(self) -> Result<Pool, ConnectionError> {
+		let inner = Arc::new(PoolInner {
+			config: self,
+			state: Mutex::new(PoolState {idle: VecDeque::new(), total: 0}),
+			cond: Condvar::new(),
+		});
+		
+		try!(inner.fill_idle());
+		
+		let weak = Arc::downgrade(&inner);
+		try!(thread::Builder::new().name(String::from("recogitate-pool-reaper")).spawn(move || reap_loop(weak)));
+		
+		Ok(Pool {inner: inner})
+	}
+}
+
+struct Entry {
+	conn: Connection,
+	created: Instant,
+	last_used: Instant,
+}
+
+struct PoolState {
+	idle: VecDeque<Entry>,
+	/// Idle, checked out and currently connecting
+	total: usize,
+}
+
+struct PoolInner {
+	config: PoolBuilder,
+	state: Mutex<PoolState>,
+	cond: Condvar,
+}
+
+impl PoolInner {
+	fn is_expired(&self, entry: &Entry, now: Instant) -> bool {
+		match self.config.max_lifetime {
+			Some(lifetime) => now.duration_since(entry.created) >= lifetime,
+			None => false,
+		}
+	}
+	
+	fn connect(&self) -> Result<Entry, ConnectionError> {
+		let conn = try!(self.config.builder.clone().connect());
+		let now = Instant::now();
+		Ok(Entry {conn: conn, created: now, last_used: now})
+	}
+	
+	/// Closes expired connections and those idle past the idle timeout
+	fn reap(&self) {
+		let now = Instant::now();
+		let mut state = self.state.lock().unwrap();
+		let min_idle = self.config.min_idle;
+		let mut kept = VecDeque::new();
+		
+		while let Some(entry) = state.idle.pop_front() {
+			let idle_too_long = match self.config.idle_timeout {
+				Some(timeout) => now.duration_since(entry.last_used) >= timeout,
+				None => false,
+			};
+			
+			if self.is_expired(&entry, now) || (idle_too_long && kept.len() + state.idle.len() >= min_idle) {
+				state.total -= 1;
+			} else {
+				kept.push_back(entry);
+			}
+		}
+		
+		state.idle = kept;
+		self.cond.notify_all();
+	}
+	
+	/// Opens connections until there are `min_idle` idle, or the pool is full
+	fn fill_idle(&self) -> Result<(), ConnectionError> {
+		loop {
+			{
+				let mut state = self.state.lock().unwrap();
+				if state.idle.len() >= self.config.min_idle || state.total >= self.config.max_size {
+					return Ok(());
+				}
+				state.total += 1;
+			}
+			
+			match self.connect() {
+				Ok(entry) => {
+					let mut state = self.state.lock().unwrap();
+					state.idle.push_back(entry);
+					self.cond.notify_one();
+				},
+				Err(e) => {
+					self.state.lock().unwrap().total -= 1;
+					return Err(e);
+				},
+			}
+		}
+	}
+	
+	/// Returns a connection to the idle list, undoing any settings changed while it was checked
+	/// out. Closed and expired connections are dropped instead.
+	fn release(&self, mut entry: Entry) {
+		let now = Instant::now();
+		
+		if entry.conn.is_open() && !self.is_expired(&entry, now) {
+			let builder = &self.config.builder;
+			entry.conn.set_read_timeout(builder.read_timeout);
+			entry.conn.set_write_timeout(builder.write_timeout);
+			entry.conn.use_db(builder.dbname.clone());
+			entry.last_used = now;
+			self.state.lock().unwrap().idle.push_back(entry);
+		} else {
+			self.state.lock().unwrap().total -= 1;
+		}
+		
+		self.cond.notify_one();
+	}
+}
+
+fn reap_loop(pool: Weak<PoolInner>) {
+	loop {
+		let interval = match pool.upgrade() {
+			Some(inner) => {
+				inner.reap();
+				let _ = inner.fill_idle();
+				inner.config.idle_timeout.unwrap_or(Duration::from_secs(30)) / 2
+			},
+			None => return,
+		};
+		
+		thread::sleep(::std::cmp::max(interval, Duration::from_secs(1)));
+	}
+}
+
+/// A bounded set of authenticated connections shared between threads
+#[derive(Clone)]
+pub struct Pool {
+	inner: Arc<PoolInner>,
+}
+
+impl Pool {
+	/// Checks out a connection, opening a new one if none are idle and the pool is not full.
+	/// Fails with `ConnectionError::Timeout` if none becomes available within the checkout timeout.
+	pub fn get(&self) -> Result<PooledConnection, ConnectionError> {
+		let deadline = Instant::now() + self.inner.config.checkout_timeout;
+		
+		loop {
+			let mut state = self.inner.state.lock().unwrap();
+			
+			if let Some(entry) = state.idle.pop_back() {
+				drop(state);
+				
+				let now = Instant::now();
+				if self.inner.is_expired(&entry, now) {
+					self.inner.state.lock().unwrap().total -= 1;
+					self.inner.cond.notify_one();
+					continue;
+				}
+				
+				//bounded by the checkout deadline, as a half-dead socket may never answer
+				if self.inner.config.health_check {
+					if let Err(e) = entry.conn.server_info_until(Some(deadline)) {
+						self.inner.state.lock().unwrap().total -= 1;
+						self.inner.cond.notify_one();
+						
+						match e {
+							QueryError::ConnectionError(ConnectionError::Timeout) => return Err(ConnectionError::Timeout),
+							_ => continue,
+						}
+					}
+				}
+				
+				return Ok(PooledConnection {pool: self.inner.clone(), entry: Some(entry)});
+			}
+			
+			if state.total < self.inner.config.max_size {
+				state.total += 1;
+				drop(state);
+				
+				return match self.inner.connect() {
+					Ok(entry) => Ok(PooledConnection {pool: self.inner.clone(), entry: Some(entry)}),
+					Err(e) => {
+						self.inner.state.lock().unwrap().total -= 1;
+						self.inner.cond.notify_one();
+						Err(e)
+					},
+				};
+			}
+			
+			let now = Instant::now();
+			if now >= deadline {
+				return Err(ConnectionError::Timeout);
+			}
+			
+			let _ = self.inner.cond.wait_timeout(state, deadline - now).unwrap();
+		}
+	}
+	
+	/// The number of open connections that are not checked out
+	pub fn idle_connections(&self) -> usize {
+		self.inner.state.lock().unwrap().idle.len()
+	}
+	
+	/// The number of open connections, including those checked out
+	pub fn connections(&self) -> usize {
+		self.inner.state.lock().unwrap().total
+	}
+}
+
+/// A connection checked out of a `Pool`, returned to it when dropped
+pub struct PooledConnection {
+	pool: Arc<PoolInner>,
+	entry: Option<Entry>,
+}
+
+impl PooledConnection {
+	/// Closes the connection instead of returning it to the pool, for use after an error has
+	/// left it in an unknown state
+	pub fn discard(mut self) {
+		if self.entry.take().is_some() {
+			self.pool.state.lock().unwrap().total -= 1;
+			self.pool.cond.notify_one();
+		}
+	}
+}
+
+impl Deref for PooledConnection {
+	type Target = Connection;
+	
+	fn deref(&self) -> &Connection {
+		&self.entry.as_ref().unwrap().conn
+	}
+}
+
+impl Drop for PooledConnection {
+	fn drop(&mut self) {
+		if let Some(entry) = self.entry.take() {
+			self.pool.release(entry);
+		}
+	}
+}
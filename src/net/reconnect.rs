@@ -0,0 +1,202 @@
+use std::cmp;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use err::ConnectionError;
+use super::{Connection, ConnectionBuilder};
+
+/// Settings for a `Reconnecting` connection, created with `ConnectionBuilder::reconnecting`
+pub struct ReconnectBuilder {
+	builder: ConnectionBuilder,
+	initial_backoff: Duration,
+	max_backoff: Duration,
+	max_attempts: Option<u32>,
+}
+
+impl ReconnectBuilder {
+	pub fn new(builder: ConnectionBuilder) -> ReconnectBuilder {
+		ReconnectBuilder {
+			builder: builder,
+			initial_backoff: Duration::from_millis(100),
+			max_backoff: Duration::from_secs(30),
+			max_attempts: Some(10),
+		}
+	}
+	
+	/// How long to wait after the first failed attempt. The wait doubles after each further failure.
+	pub fn initial_backoff(mut self, val: Duration) -> ReconnectBuilder {
+		self.initial_backoff = val;
+		self
+	}
+	
+	/// The longest to wait between attempts
+	pub fn max_backoff(mut self, val: Duration) -> ReconnectBuilder {
+		self.max_backoff = val;
+		self
+	}
+	
+	/// How many attempts to make before giving up, or `None` to keep trying forever
+	pub fn max_attempts(mut self, val: Option<u32>) -> ReconnectBuilder {
+		self.max_attempts = val;
+		self
+	}
+	
+	/// Opens the first connection, retrying with the same backoff as later reconnects
+	pub fn build(self) -> Result<Reconnecting, ConnectionError> {
+		let conn = try!(self.connect(&self.builder.dbname));
+		
+		Ok(Reconnecting {
+			inner: Arc::new(ReconnectInner {
+				config: self,
+				state: Mutex::new(ReconnectState {conn: Arc::new(conn), reconnects: 0, reconnecting: false}),
+				reconnected: Condvar::new(),
+			}),
+		})
+	}
+	
+	/// Connects and repeats the handshake, backing off after each failure that might be
+	/// temporary. Authentication and protocol errors are returned immediately.
+	fn connect(&self, db: &str) -> Result<Connection, ConnectionError> {
+		let mut backoff = self.initial_backoff;
+		let mut attempts = 0;
+		
+		loop {
+			let err = match self.builder.clone().dbname(db.to_owned()).connect() {
+				Ok(conn) => return Ok(conn),
+				Err(e) => e,
+			};
+			
+			attempts += 1;
+			let exhausted = match self.max_attempts {
+				Some(max) => attempts >= max,
+				None => false,
+			};
+			
+			if exhausted || !is_transient(&err) {
+				return Err(err);
+			}
+			
+			thread::sleep(backoff);
+			backoff = cmp::min(backoff * 2, self.max_backoff);
+		}
+	}
+}
+
+fn is_transient(err: &ConnectionError) -> bool {
+	match *err {
+		ConnectionError::Io(_) | ConnectionError::Timeout => true,
+		_ => false,
+	}
+}
+
+struct ReconnectState {
+	conn: Arc<Connection>,
+	reconnects: u64,
+	/// Set while one thread is reconnecting without holding the lock
+	reconnecting: bool,
+}
+
+struct ReconnectInner {
+	config: ReconnectBuilder,
+	state: Mutex<ReconnectState>,
+	/// Notified when a reconnect attempt finishes, whether or not it succeeded
+	reconnected: Condvar,
+}
+
+/// A connection that is transparently reopened after its transport fails.
+///
+/// Queries in flight when the connection drops still fail, but the next call to `get` opens a
+/// new connection with the original settings and the database last selected with `use_db`. Changefeeds run with `Changes::run_resumable`
+/// subscribe again by themselves.
+#[derive(Clone)]
+pub struct Reconnecting {
+	inner: Arc<ReconnectInner>,
+}
+
+impl Reconnecting {
+	/// The current connection, reopening it first if it has been closed.
+	/// Only one thread reconnects at a time; others wait for it to finish.
+	pub fn get(&self) -> Result<Arc<Connection>, ConnectionError> {
+		let mut state = self.inner.state.lock().unwrap();
+		
+		loop {
+			if state.conn.is_open() {
+				return Ok(state.conn.clone());
+			}
+			
+			if !state.reconnecting {
+				break;
+			}
+			
+			state = self.inner.reconnected.wait(state).unwrap();
+		}
+		
+		//the lock is released while backing off, so `reconnects` and `get` never wait on it
+		state.reconnecting = true;
+		let db = state.conn.default_db();
+		drop(state);
+		
+		let result = self.inner.config.connect(&db);
+		
+		let mut state = self.inner.state.lock().unwrap();
+		state.reconnecting = false;
+		self.inner.reconnected.notify_all();
+		
+		let conn = Arc::new(try!(result));
+		state.conn = conn.clone();
+		state.reconnects += 1;
+		Ok(conn)
+	}
+	
+	/// The number of times the connection has been reopened
+	pub fn reconnects(&self) -> u64 {
+		self.inner.state.lock().unwrap().reconnects
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::thread;
+	use std::time::Duration;
+	use err::ConnectionError;
+	use net::{connection, Socket, Transport};
+	
+	#[test]
+	fn test_reconnecting() {
+		let conn = connection().reconnecting().build().unwrap();
+		let first = conn.get().unwrap();
+		
+		//an open connection is reused
+		assert!(Arc::ptr_eq(&first, &conn.get().unwrap()));
+		assert_eq!(conn.reconnects(), 0);
+		
+		//a closed one is replaced, keeping the database picked with `use_db`
+		first.use_db(String::from("other"));
+		Socket::shutdown(&first.writer.lock().unwrap()).unwrap();
+		while first.is_open() {
+			thread::sleep(Duration::from_millis(10));
+		}
+		
+		let second = conn.get().unwrap();
+		assert!(!Arc::ptr_eq(&first, &second));
+		assert_eq!(second.default_db(), "other");
+		assert_eq!(conn.reconnects(), 1);
+	}
+	
+	#[test]
+	fn test_reconnect_gives_up() {
+		let result = connection()
+			.port(1)
+			.reconnecting()
+			.initial_backoff(Duration::from_millis(1))
+			.max_attempts(Some(3))
+			.build();
+		
+		match result {
+			Err(ConnectionError::Io(_)) => (),
+			Err(e) => panic!("expected the last connection error, got {:?}", e),
+			Ok(_) => panic!("expected connecting to fail"),
+		}
+	}
+}
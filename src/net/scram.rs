@@ -1,7 +1,7 @@
-use openssl;
-use openssl::crypto::hmac;
 use openssl::crypto::hash;
-use openssl::crypto::pkcs5::pbkdf2_hmac_sha256;
+use openssl::crypto::pkcs5::pbkdf2_hmac;
+use openssl::crypto::rand::rand_bytes;
+use openssl::error::ErrorStack;
 use rustc_serialize::base64::{self,ToBase64,FromBase64};
 use rustc_serialize::json::Json;
 use std::collections::BTreeMap;
@@ -27,8 +27,9 @@ fn sanitise(user: &String) -> String {
 	user.replace("=", "=3D").replace(",", "=2C")
 }
 
-pub fn begin_handshake(user: &String, pass: &String) -> (Json, HandshakeA) {
-	let nonce_bytes = openssl::crypto::rand::rand_bytes(NONCE_LEN);
+pub fn begin_handshake(user: &String, pass: &String) -> Result<(Json, HandshakeA), ConnectionError> {
+	let mut nonce_bytes = [0u8; NONCE_LEN];
+	try!(rand_bytes(&mut nonce_bytes));
 	let nonce = nonce_bytes.to_base64(base64::Config{
 		char_set: base64::CharacterSet::Standard,
 		newline: base64::Newline::LF,
@@ -45,7 +46,28 @@ pub fn begin_handshake(user: &String, pass: &String) -> (Json, HandshakeA) {
 		"authentication".to_owned() => Json::String(auth_str)
 	};
 	
-	(Json::Object(msg_obj), HandshakeA {user: user.clone(), pass: pass.clone(), nonce: nonce, client_first_message_bare: bare_message})
+	Ok((Json::Object(msg_obj), HandshakeA {user: user.clone(), pass: pass.clone(), nonce: nonce, client_first_message_bare: bare_message}))
+}
+
+/// HMAC-SHA-256 as defined in RFC 2104. The openssl crate only provides HMAC through a C helper
+/// library, so it is built from the hash function here.
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+	const BLOCK_LEN: usize = 64;
+	
+	let mut block = if key.len() > BLOCK_LEN {
+		try!(hash::hash(hash::Type::SHA256, key))
+	} else {
+		key.to_vec()
+	};
+	block.resize(BLOCK_LEN, 0);
+	
+	let mut inner = block.iter().map(|b| b ^ 0x36).collect::<Vec<_>>();
+	inner.extend_from_slice(data);
+	let inner_hash = try!(hash::hash(hash::Type::SHA256, &inner));
+	
+	let mut outer = block.iter().map(|b| b ^ 0x5c).collect::<Vec<_>>();
+	outer.extend_from_slice(&inner_hash);
+	hash::hash(hash::Type::SHA256, &outer)
 }
 
 fn string_xor<'a, T1, T2>(a: T1, b: T2) -> Vec<u8>
@@ -113,15 +135,15 @@ impl HandshakeA {
 		
 		let client_final_message_without_proof = format!("c=biws,r={}", new_nonce); //biws is base64("n,,")
 		
-		let salted_password = pbkdf2_hmac_sha256(&self.pass, &salt, iterations as usize, 32);
+		let salted_password = try!(pbkdf2_hmac(self.pass.as_bytes(), &salt, iterations as usize, hash::Type::SHA256, 32));
 		//println!("{}", salted_password.len());
-		let client_key = hmac::hmac(hash::Type::SHA256, &salted_password, "Client Key".as_bytes());
-		let stored_key = hash::hash(hash::Type::SHA256, &client_key);
+		let client_key = try!(hmac_sha256(&salted_password, "Client Key".as_bytes()));
+		let stored_key = try!(hash::hash(hash::Type::SHA256, &client_key));
 		let auth_message = format!("{},{},{}",
 			self.client_first_message_bare,
 			auth_str,
 			client_final_message_without_proof);
-		let client_signature = hmac::hmac(hash::Type::SHA256, &stored_key, auth_message.as_bytes());
+		let client_signature = try!(hmac_sha256(&stored_key, auth_message.as_bytes()));
 		let client_proof = string_xor(&client_key, &client_signature);
 		let client_proof_b64 = client_proof.to_base64(base64::Config{
 			char_set: base64::CharacterSet::Standard,
@@ -135,8 +157,8 @@ impl HandshakeA {
 			"authentication".to_owned() => Json::String(client_final_message)
 		};
 		
-		let server_key = hmac::hmac(hash::Type::SHA256, &salted_password, "Server Key".as_bytes());
-		let server_signature = hmac::hmac(hash::Type::SHA256, &server_key, auth_message.as_bytes());
+		let server_key = try!(hmac_sha256(&salted_password, "Server Key".as_bytes()));
+		let server_signature = try!(hmac_sha256(&server_key, auth_message.as_bytes()));
 		
 		Ok((Json::Object(msg_obj), HandshakeB {expected_server_signature: server_signature}))
 	}
@@ -201,7 +223,7 @@ mod test {
 		let user = String::from("testuser");
 		let pass = String::from("hunter2");
 		
-		let (msg, handshake_a) = begin_handshake(&user, &pass);
+		let (msg, handshake_a) = begin_handshake(&user, &pass).unwrap();
 		
 		println!("{}", msg.pretty());
 		//panic!();
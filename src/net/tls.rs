@@ -0,0 +1,457 @@
+use openssl::error::ErrorStack;
+use openssl::ssl::{self, HandshakeError, Ssl, SslContext, SslMethod, SslStream};
+use openssl::ssl::Error as SslError;
+use openssl::x509::{X509Ref, X509FileType, X509StoreContext};
+use openssl::nid::Nid;
+use std::io::prelude::*;
+use std::io;
+use std::net::{Shutdown, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use err::ConnectionError;
+use super::Transport;
+
+pub type TlsStream = SslStream<TcpStream>;
+
+/// The oldest protocol version that will be negotiated with the server
+#[derive(Debug,Copy,Clone,PartialEq,Eq,PartialOrd,Ord)]
+pub enum TlsVersion {
+	Tlsv1_0,
+	Tlsv1_1,
+	Tlsv1_2,
+}
+
+/// TLS settings for `ConnectionBuilder::tls`
+#[derive(Debug,Clone)]
+pub struct TlsOptions {
+	ca_file: PathBuf,
+	client_cert: Option<(PathBuf, PathBuf)>,
+	verify_hostname: bool,
+	min_version: TlsVersion,
+}
+
+impl TlsOptions {
+	/// Trusts the certificate authorities in the PEM bundle at `ca_file`
+	pub fn new(ca_file: PathBuf) -> TlsOptions {
+		TlsOptions {
+			ca_file: ca_file,
+			client_cert: None,
+			verify_hostname: true,
+			min_version: TlsVersion::Tlsv1_2,
+		}
+	}
+	
+	/// Presents a client certificate for mutual TLS; both files are PEM encoded
+	pub fn client_cert(mut self, cert_file: PathBuf, key_file: PathBuf) -> TlsOptions {
+		self.client_cert = Some((cert_file, key_file));
+		self
+	}
+	
+	/// Controls whether SNI is sent and the server certificate is checked against the hostname.
+	/// The certificate chain is always verified against the CA bundle.
+	pub fn verify_hostname(mut self, val: bool) -> TlsOptions {
+		self.verify_hostname = val;
+		self
+	}
+	
+	pub fn min_version(mut self, val: TlsVersion) -> TlsOptions {
+		self.min_version = val;
+		self
+	}
+	
+	fn build_context(&self, hostname: &str) -> Result<SslContext, ErrorStack> {
+		let mut ctx = try!(SslContext::new(SslMethod::Sslv23));
+		
+		//the bindings have no option to turn off TLS 1.1, so `connect` checks the negotiated version
+		let mut options = ssl::SSL_OP_NO_SSLV2 | ssl::SSL_OP_NO_SSLV3;
+		if self.min_version >= TlsVersion::Tlsv1_1 {
+			options = options | ssl::SSL_OP_NO_TLSV1;
+		}
+		ctx.set_options(options);
+		
+		try!(ctx.set_CA_file(&self.ca_file));
+		
+		if let Some((ref cert_file, ref key_file)) = self.client_cert {
+			try!(ctx.set_certificate_file(cert_file, X509FileType::PEM));
+			try!(ctx.set_private_key_file(key_file, X509FileType::PEM));
+			try!(ctx.check_private_key());
+		}
+		
+		if self.verify_hostname {
+			let hostname = hostname.to_owned();
+			ctx.set_verify_callback(ssl::SSL_VERIFY_PEER, move |preverify_ok, x509_ctx: &X509StoreContext| {
+				//only the leaf certificate carries the server's name
+				if !preverify_ok || x509_ctx.error_depth() != 0 {
+					return preverify_ok;
+				}
+				
+				match x509_ctx.current_cert() {
+					Some(cert) => cert_matches_hostname(&cert, &hostname),
+					None => false,
+				}
+			});
+		} else {
+			ctx.set_verify(ssl::SSL_VERIFY_PEER);
+		}
+		
+		Ok(ctx)
+	}
+}
+
+/// Checks the certificate's DNS subject alternative names, falling back to the common name
+/// if it has none
+fn cert_matches_hostname(cert: &X509Ref, hostname: &str) -> bool {
+	if let Some(names) = cert.subject_alt_names() {
+		let dns_names = (0..names.len()).filter_map(|i| names.get(i).dnsname().map(|s| s.to_owned())).collect::<Vec<_>>();
+		
+		if !dns_names.is_empty() {
+			return dns_names.iter().any(|name| name_matches(name, hostname));
+		}
+	}
+	
+	match cert.subject_name().text_by_nid(Nid::CN) {
+		Some(cn) => name_matches(&cn, hostname),
+		None => false,
+	}
+}
+
+/// Case-insensitive comparison allowing a single leading `*.` wildcard label
+fn name_matches(pattern: &str, hostname: &str) -> bool {
+	let pattern = pattern.to_lowercase();
+	let hostname = hostname.to_lowercase();
+	
+	if pattern.starts_with("*.") {
+		match hostname.find('.') {
+			Some(idx) => idx > 0 && hostname[idx..] == pattern[1..],
+			None => false,
+		}
+	} else {
+		pattern == hostname
+	}
+}
+
+/// Checks a protocol name reported by OpenSSL against the minimum version
+fn meets_min_version(negotiated: &str, min_version: TlsVersion) -> bool {
+	match negotiated {
+		"TLSv1" => min_version <= TlsVersion::Tlsv1_0,
+		"TLSv1.1" => min_version <= TlsVersion::Tlsv1_1,
+		"SSLv2" | "SSLv3" | "unknown" => false,
+		_ => true,
+	}
+}
+
+/// Performs the TLS handshake over an established TCP connection
+pub fn connect(options: &TlsOptions, hostname: &str, stream: TcpStream) -> Result<TlsStream, ConnectionError> {
+	let ctx = try!(options.build_context(hostname));
+	let mut ssl = try!(Ssl::new(&ctx));
+	
+	if options.verify_hostname {
+		try!(ssl.set_hostname(hostname));
+	}
+	
+	let stream = try!(SslStream::connect(ssl, stream).map_err(|e| match e {
+		//the handshake runs with the connection deadline set on the socket
+		HandshakeError::Failure(SslError::Stream(e)) => super::timed_io_error(e),
+		HandshakeError::Failure(e) => ConnectionError::Tls(e),
+		//a blocking socket only interrupts the handshake once its timeout elapses
+		HandshakeError::Interrupted(_) => ConnectionError::Timeout,
+	}));
+	
+	let version = stream.ssl().version();
+	if !meets_min_version(version, options.min_version) {
+		return Err(ConnectionError::Io(io::Error::new(io::ErrorKind::Other, format!("The server negotiated {}, which is older than the minimum TLS version", version))));
+	}
+	
+	Ok(stream)
+}
+
+/// One side of a `TlsStream` shared between a connection's reader and its writers.
+///
+/// An SSL stream cannot be read and written from different threads at once, so both sides lock
+/// it. The reader never blocks while holding the lock: it reads without blocking, and when
+/// OpenSSL needs more of a record it waits for data on its own handle to the socket after
+/// releasing the lock, so writers are never held up by an idle connection or a partial record.
+pub struct TlsHalf {
+	stream: Arc<Mutex<TlsStream>>,
+	socket: TcpStream,
+}
+
+impl Read for TlsHalf {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		loop {
+			{
+				//writers only use the socket with the lock held, so they never see it non-blocking
+				let mut stream = self.stream.lock().unwrap();
+				try!(self.socket.set_nonblocking(true));
+				let result = stream.ssl_read(buf);
+				try!(self.socket.set_nonblocking(false));
+				
+				match result {
+					Ok(n) => return Ok(n),
+					Err(SslError::ZeroReturn) => return Ok(0),
+					//the socket is drained, possibly partway through a record or after a record
+					//with no application data, such as a session ticket
+					Err(SslError::WantRead(_)) => {},
+					//rare: OpenSSL has a message of its own to send and the send buffer is full.
+					//There is no way to wait for the socket to become writable, so back off briefly.
+					Err(SslError::WantWrite(_)) => {
+						drop(stream);
+						thread::sleep(Duration::from_millis(1));
+						continue;
+					},
+					Err(SslError::Stream(e)) => return Err(e),
+					Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+				}
+			}
+			
+			//blocks until the peer sends something or closes the connection, or fails once the
+			//socket's read timeout elapses
+			let mut byte = [0u8; 1];
+			try!(self.socket.peek(&mut byte));
+		}
+	}
+}
+
+impl Write for TlsHalf {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.stream.lock().unwrap().write(buf)
+	}
+	
+	fn flush(&mut self) -> io::Result<()> {
+		self.stream.lock().unwrap().flush()
+	}
+}
+
+impl TlsHalf {
+	pub fn set_write_timeout(&self, val: Option<Duration>) -> io::Result<()> {
+		self.socket.set_write_timeout(val)
+	}
+	
+	pub fn shutdown(&self) -> io::Result<()> {
+		//does not need the lock, so it also wakes a reader blocked in the middle of a record
+		self.socket.shutdown(Shutdown::Both)
+	}
+}
+
+impl Transport for TlsStream {
+	type Reader = TlsHalf;
+	type Writer = TlsHalf;
+	
+	fn set_read_timeout(&self, val: Option<Duration>) -> io::Result<()> {
+		self.get_ref().set_read_timeout(val)
+	}
+	
+	fn set_write_timeout(&self, val: Option<Duration>) -> io::Result<()> {
+		self.get_ref().set_write_timeout(val)
+	}
+	
+	fn split(self) -> io::Result<(TlsHalf, TlsHalf)> {
+		//the handshake's deadline no longer applies; socket options are shared by every handle to
+		//the socket, so the clones see any timeout set later
+		try!(self.get_ref().set_read_timeout(None));
+		let reader_socket = try!(self.get_ref().try_clone());
+		let writer_socket = try!(self.get_ref().try_clone());
+		
+		let stream = Arc::new(Mutex::new(self));
+		let reader = TlsHalf {stream: stream.clone(), socket: reader_socket};
+		let writer = TlsHalf {stream: stream, socket: writer_socket};
+		Ok((reader, writer))
+	}
+	
+	fn set_writer_timeout(writer: &TlsHalf, val: Option<Duration>) -> io::Result<()> {
+		writer.set_write_timeout(val)
+	}
+	
+	fn shutdown(writer: &TlsHalf) -> io::Result<()> {
+		writer.shutdown()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use openssl::ssl::{SslContext, SslMethod, SslStream};
+	use openssl::x509::{X509, X509FileType};
+	use std::fs::File;
+	use std::io::prelude::*;
+	use std::net::{TcpListener, TcpStream};
+	use std::path::{Path, PathBuf};
+	use std::sync::mpsc;
+	use std::thread;
+	use std::time::Duration;
+	use err::ConnectionError;
+	use super::super::Transport;
+	use super::{connect, cert_matches_hostname, meets_min_version, name_matches, TlsOptions, TlsVersion};
+	
+	/// The test certificates: `server.pem` is issued for `localhost` by `ca.pem`, and
+	/// `other-ca.pem` is an unrelated authority
+	fn test_file(name: &str) -> PathBuf {
+		Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/tls").join(name)
+	}
+	
+	fn read_file(name: &str) -> Vec<u8> {
+		let mut buf = Vec::new();
+		File::open(test_file(name)).unwrap().read_to_end(&mut buf).unwrap();
+		buf
+	}
+	
+	/// Accepts one connection on a local port and echoes back the first four bytes it receives
+	/// over TLS. A failed handshake just ends the thread.
+	fn start_server() -> u16 {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let port = listener.local_addr().unwrap().port();
+		
+		let mut ctx = SslContext::new(SslMethod::Sslv23).unwrap();
+		ctx.set_certificate_file(&test_file("server.pem"), X509FileType::PEM).unwrap();
+		ctx.set_private_key_file(&test_file("server.key"), X509FileType::PEM).unwrap();
+		
+		thread::spawn(move || {
+			let (socket, _) = listener.accept().unwrap();
+			if let Ok(mut stream) = SslStream::accept(&ctx, socket) {
+				let mut buf = [0u8; 4];
+				if stream.read_exact(&mut buf).is_ok() {
+					let _ = stream.write_all(&buf);
+					let _ = stream.flush();
+				}
+			}
+		});
+		
+		port
+	}
+	
+	fn connect_local(options: &TlsOptions, hostname: &str, port: u16) -> Result<super::TlsStream, ConnectionError> {
+		let socket = TcpStream::connect(("127.0.0.1", port)).unwrap();
+		socket.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+		connect(options, hostname, socket)
+	}
+	
+	#[test]
+	fn test_meets_min_version() {
+		assert!(meets_min_version("TLSv1.2", TlsVersion::Tlsv1_2));
+		assert!(meets_min_version("TLSv1.3", TlsVersion::Tlsv1_2));
+		assert!(meets_min_version("TLSv1", TlsVersion::Tlsv1_0));
+		assert!(!meets_min_version("TLSv1.1", TlsVersion::Tlsv1_2));
+		assert!(!meets_min_version("SSLv3", TlsVersion::Tlsv1_0));
+	}
+	
+	#[test]
+	fn test_build_context() {
+		assert!(TlsOptions::new(test_file("ca.pem")).build_context("localhost").is_ok());
+		assert!(TlsOptions::new(test_file("missing.pem")).build_context("localhost").is_err());
+		
+		let options = TlsOptions::new(test_file("ca.pem")).client_cert(test_file("server.pem"), test_file("server.key"));
+		assert!(options.build_context("localhost").is_ok());
+		
+		//the key does not belong to the certificate
+		let options = TlsOptions::new(test_file("ca.pem")).client_cert(test_file("ca.pem"), test_file("server.key"));
+		assert!(options.build_context("localhost").is_err());
+	}
+	
+	#[test]
+	fn test_cert_matches_hostname() {
+		let cert = X509::from_pem(&read_file("server.pem")).unwrap();
+		assert!(cert_matches_hostname(&cert, "localhost"));
+		assert!(cert_matches_hostname(&cert, "LOCALHOST"));
+		assert!(!cert_matches_hostname(&cert, "db.example.com"));
+		
+		//no subject alternative names, so the common name is used
+		let cert = X509::from_pem(&read_file("ca.pem")).unwrap();
+		assert!(cert_matches_hostname(&cert, "recogitate test CA"));
+		assert!(!cert_matches_hostname(&cert, "localhost"));
+	}
+	
+	#[test]
+	fn test_handshake() {
+		let port = start_server();
+		let stream = connect_local(&TlsOptions::new(test_file("ca.pem")), "localhost", port).unwrap();
+		
+		let (mut reader, mut writer) = stream.split().unwrap();
+		writer.write_all(b"ping").unwrap();
+		writer.flush().unwrap();
+		
+		let mut buf = [0u8; 4];
+		reader.read_exact(&mut buf).unwrap();
+		assert_eq!(&buf, b"ping");
+	}
+	
+	#[test]
+	fn test_write_during_read() {
+		let port = start_server();
+		let stream = connect_local(&TlsOptions::new(test_file("ca.pem")), "localhost", port).unwrap();
+		let (mut reader, mut writer) = stream.split().unwrap();
+		
+		//the reader is already waiting when the request goes out, and the server's session
+		//tickets arrive before the reply
+		let (tx, rx) = mpsc::channel();
+		let read_tx = tx.clone();
+		thread::spawn(move || {
+			let mut buf = [0u8; 4];
+			let _ = read_tx.send(reader.read_exact(&mut buf).map(|_| buf.to_vec()));
+		});
+		thread::sleep(Duration::from_millis(100));
+		
+		thread::spawn(move || {
+			if let Err(e) = writer.write_all(b"ping").and_then(|_| writer.flush()) {
+				let _ = tx.send(Err(e));
+			}
+		});
+		
+		let reply = rx.recv_timeout(Duration::from_secs(10)).expect("the reader blocked the writer");
+		assert_eq!(reply.unwrap(), b"ping");
+	}
+	
+	#[test]
+	fn test_wrong_hostname() {
+		let port = start_server();
+		match connect_local(&TlsOptions::new(test_file("ca.pem")), "db.example.com", port) {
+			Err(ConnectionError::Tls(_)) => {},
+			Err(e) => panic!("unexpected error {:?}", e),
+			Ok(_) => panic!("connected to a server with the wrong name"),
+		}
+		
+		//the chain is still checked when the hostname is not
+		let port = start_server();
+		let options = TlsOptions::new(test_file("ca.pem")).verify_hostname(false);
+		assert!(connect_local(&options, "db.example.com", port).is_ok());
+	}
+	
+	#[test]
+	fn test_untrusted_ca() {
+		let port = start_server();
+		match connect_local(&TlsOptions::new(test_file("other-ca.pem")), "localhost", port) {
+			Err(ConnectionError::Tls(_)) => {},
+			Err(e) => panic!("unexpected error {:?}", e),
+			Ok(_) => panic!("connected to a server with an untrusted certificate"),
+		}
+		
+		let port = start_server();
+		let options = TlsOptions::new(test_file("other-ca.pem")).verify_hostname(false);
+		assert!(connect_local(&options, "localhost", port).is_err());
+	}
+	
+	#[test]
+	fn test_plaintext_server() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let port = listener.local_addr().unwrap().port();
+		thread::spawn(move || {
+			let (mut socket, _) = listener.accept().unwrap();
+			let _ = socket.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+		});
+		
+		match connect_local(&TlsOptions::new(test_file("ca.pem")), "localhost", port) {
+			Err(ConnectionError::Timeout) => panic!("the handshake waited for the timeout"),
+			Err(_) => {},
+			Ok(_) => panic!("completed a handshake with a plaintext server"),
+		}
+	}
+	
+	#[test]
+	fn test_name_matches() {
+		assert!(name_matches("db.example.com", "DB.example.com"));
+		assert!(name_matches("*.example.com", "db.example.com"));
+		assert!(!name_matches("*.example.com", "example.com"));
+		assert!(!name_matches("*.example.com", "a.db.example.com"));
+		assert!(!name_matches("db.example.com", "db.example.org"));
+	}
+}
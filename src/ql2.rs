@@ -0,0 +1,538 @@
+//! The ReQL wire protocol vocabulary, as defined in `ql2.proto`.
+//!
+//! Variant names and values match the protocol definition exactly, so they can be
+//! cross-referenced against the official drivers.
+
+macro_rules! ql2_enum {
+	(
+		$(#[$attr:meta])*
+		pub enum $name:ident {
+			$($(#[$vattr:meta])* $variant:ident = $val:expr,)*
+		}
+	) => {
+		$(#[$attr])*
+		#[allow(non_camel_case_types)]
+		#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+		pub enum $name {
+			$($(#[$vattr])* $variant = $val,)*
+		}
+		
+		impl $name {
+			/// Converts a wire integer into the matching variant
+			pub fn from_u64(val: u64) -> Option<$name> {
+				match val {
+					$(x if x == $val => Some($name::$variant),)*
+					_ => None,
+				}
+			}
+		}
+	}
+}
+
+ql2_enum! {
+	/// The type of a query packet, sent as the first element of the query array
+	pub enum QueryType {
+		/// Start a new query
+		START = 1,
+		/// Fetch the next batch of a partial result
+		CONTINUE = 2,
+		/// Discard the remaining results of a partial result
+		STOP = 3,
+		/// Wait for all outstanding noreply queries to complete
+		NOREPLY_WAIT = 4,
+		/// Request information about the server the connection is attached to
+		SERVER_INFO = 5,
+	}
+}
+
+ql2_enum! {
+	/// The kind of reply sent by the server, taken from the `t` field of the response
+	pub enum ResponseType {
+		/// The whole result is a single datum in `r[0]`
+		SUCCESS_ATOM = 1,
+		/// `r` holds the final batch of a sequence
+		SUCCESS_SEQUENCE = 2,
+		/// `r` holds a batch of a sequence, more can be requested with CONTINUE
+		SUCCESS_PARTIAL = 3,
+		/// All noreply queries have completed
+		WAIT_COMPLETE = 4,
+		/// `r[0]` holds the server information object
+		SERVER_INFO = 5,
+		/// The client sent a malformed query
+		CLIENT_ERROR = 16,
+		/// The query failed to compile
+		COMPILE_ERROR = 17,
+		/// The query failed while it was running
+		RUNTIME_ERROR = 18,
+	}
+}
+
+ql2_enum! {
+	/// Extra information about a sequence response, taken from the `n` field
+	pub enum ResponseNote {
+		/// The sequence is a changefeed on a table or selection
+		SEQUENCE_FEED = 1,
+		/// The sequence is a changefeed on a single document
+		ATOM_FEED = 2,
+		/// The sequence is a changefeed on an `order_by.limit` query
+		ORDER_BY_LIMIT_FEED = 3,
+		/// The sequence is a union of several changefeeds
+		UNIONED_FEED = 4,
+		/// The changefeed includes state change notifications
+		INCLUDES_STATES = 5,
+	}
+}
+
+ql2_enum! {
+	/// The class of a runtime error, taken from the `e` field of the response
+	pub enum ErrorType {
+		/// An internal server error
+		INTERNAL = 1000000,
+		/// A resource limit, such as the array size limit, was exceeded
+		RESOURCE_LIMIT = 2000000,
+		/// The query contains a logical impossibility
+		QUERY_LOGIC = 3000000,
+		/// The query referred to something that does not exist
+		NON_EXISTENCE = 3100000,
+		/// The operation failed without taking effect
+		OP_FAILED = 4100000,
+		/// The operation may or may not have taken effect
+		OP_INDETERMINATE = 4200000,
+		/// An error raised by `r.error`
+		USER = 5000000,
+		/// The user lacks the permissions required by the query
+		PERMISSION_ERROR = 6000000,
+	}
+}
+
+ql2_enum! {
+	/// The type of a term in a ReQL query tree, sent as `[type, [args...], {optargs...}]`.
+	///
+	/// The variants are grouped by area; the full signature of each term, including the optargs it
+	/// accepts, is given by the comments in `ql2.proto`.
+	pub enum TermType {
+		//Data
+		/// A literal JSON value: null, a boolean, a number or a string
+		DATUM = 1,
+		/// An array built from its arguments
+		MAKE_ARRAY = 2,
+		/// An object built from its optargs
+		MAKE_OBJ = 3,
+		/// A reference to the function parameter with the given number
+		VAR = 10,
+		/// Evaluates a JavaScript string on the server
+		JAVASCRIPT = 11,
+		/// A random UUID, or a name-based UUID for a string argument
+		UUID = 169,
+		/// Retrieves a URL over HTTP
+		HTTP = 153,
+		/// Raises a user error with the given message
+		ERROR = 12,
+		/// The implicit parameter of a function written as `r.row`
+		IMPLICIT_VAR = 13,
+		
+		//Databases and tables
+		/// A database by name
+		DB = 14,
+		/// A table by name, in the given database or the default one
+		TABLE = 15,
+		/// The document of a table with the given primary key
+		GET = 16,
+		/// The documents of a table with any of the given keys, on the primary key or an index
+		GET_ALL = 78,
+		
+		//Comparison, logic and arithmetic
+		/// True if all arguments are equal
+		EQ = 17,
+		/// True if the arguments are not all equal
+		NE = 18,
+		/// True if each argument is less than the next
+		LT = 19,
+		/// True if each argument is less than or equal to the next
+		LE = 20,
+		/// True if each argument is greater than the next
+		GT = 21,
+		/// True if each argument is greater than or equal to the next
+		GE = 22,
+		/// Logical negation
+		NOT = 23,
+		/// Sum of numbers, or concatenation of strings or arrays
+		ADD = 24,
+		/// Difference of numbers, or of times
+		SUB = 25,
+		/// Product of numbers, or an array repeated a number of times
+		MUL = 26,
+		/// Quotient of numbers
+		DIV = 27,
+		/// Remainder of an integer division
+		MOD = 28,
+		/// Rounds a number down
+		FLOOR = 183,
+		/// Rounds a number up
+		CEIL = 184,
+		/// Rounds a number to the nearest integer
+		ROUND = 185,
+		
+		//Arrays
+		/// An array with a value added to the end
+		APPEND = 29,
+		/// An array with a value added to the start
+		PREPEND = 80,
+		/// An array with every occurrence of the elements of another array removed
+		DIFFERENCE = 95,
+		/// An array treated as a set with a value added
+		SET_INSERT = 88,
+		/// The elements two arrays have in common, without duplicates
+		SET_INTERSECTION = 89,
+		/// The elements of two arrays, without duplicates
+		SET_UNION = 90,
+		/// The elements of an array not in another, without duplicates
+		SET_DIFFERENCE = 91,
+		
+		//Sequences
+		/// The elements of a sequence between two indexes
+		SLICE = 30,
+		/// A sequence without its first elements
+		SKIP = 70,
+		/// The first elements of a sequence
+		LIMIT = 71,
+		/// The indexes of the elements of a sequence that match a value or predicate
+		OFFSETS_OF = 87,
+		/// True if a sequence contains all the given values or matches all the given predicates
+		CONTAINS = 93,
+		
+		//Objects
+		/// The value of a field of an object, or of each object in a sequence
+		GET_FIELD = 31,
+		/// The field names of an object
+		KEYS = 94,
+		/// The field values of an object
+		VALUES = 186,
+		/// An object built from alternating keys and values
+		OBJECT = 143,
+		/// True if an object has all the given fields, or the objects of a sequence that do
+		HAS_FIELDS = 32,
+		/// The objects of a sequence that have all the given fields, plucked to those fields
+		WITH_FIELDS = 96,
+		/// An object with only the given fields
+		PLUCK = 33,
+		/// An object without the given fields
+		WITHOUT = 34,
+		/// Objects merged together, with later fields taking precedence
+		MERGE = 35,
+		
+		//Transformations and aggregation
+		/// The old form of BETWEEN, kept for compatibility
+		BETWEEN_DEPRECATED = 36,
+		/// The documents of a table with a key between two bounds
+		BETWEEN = 182,
+		/// A sequence combined into a single value with a two-parameter function
+		REDUCE = 37,
+		/// A function applied to each element of one or more sequences
+		MAP = 38,
+		/// A sequence combined into a value with a base, optionally emitting a sequence
+		FOLD = 187,
+		/// The elements of a sequence that match a predicate
+		FILTER = 39,
+		/// A function returning a sequence applied to each element, with the results concatenated
+		CONCAT_MAP = 40,
+		/// A sequence sorted by fields, functions or an index
+		ORDER_BY = 41,
+		/// The unique elements of a sequence
+		DISTINCT = 42,
+		/// The number of elements of a sequence, or of those that match a value or predicate
+		COUNT = 43,
+		/// True if a sequence has no elements
+		IS_EMPTY = 86,
+		/// Sequences concatenated together
+		UNION = 44,
+		/// The element of a sequence at an index
+		NTH = 45,
+		/// A field of an object or an element of a sequence, as written with `()` in the official drivers
+		BRACKET = 170,
+		
+		//Joins
+		/// The pairs of elements of two sequences for which a predicate holds
+		INNER_JOIN = 48,
+		/// As INNER_JOIN, also keeping left elements that match nothing
+		OUTER_JOIN = 49,
+		/// Elements of a sequence paired with the documents of a table whose key matches
+		EQ_JOIN = 50,
+		/// The pairs of a join merged into single objects
+		ZIP = 72,
+		/// A sequence of consecutive integers
+		RANGE = 173,
+		
+		//Array manipulation
+		/// An array with a value inserted at an index
+		INSERT_AT = 82,
+		/// An array with the element at an index, or a range of elements, removed
+		DELETE_AT = 83,
+		/// An array with the element at an index replaced
+		CHANGE_AT = 84,
+		/// An array with the elements of another array inserted at an index
+		SPLICE_AT = 85,
+		
+		//Type conversion
+		/// A value converted to another type
+		COERCE_TO = 51,
+		/// The name of the type of a value
+		TYPE_OF = 52,
+		
+		//Writes
+		/// Updates documents with an object or function
+		UPDATE = 53,
+		/// Deletes documents
+		DELETE = 54,
+		/// Replaces documents with an object or function
+		REPLACE = 55,
+		/// Inserts documents into a table
+		INSERT = 56,
+		
+		//Administration
+		/// Creates a database
+		DB_CREATE = 57,
+		/// Drops a database and its tables
+		DB_DROP = 58,
+		/// The names of the databases
+		DB_LIST = 59,
+		/// Creates a table
+		TABLE_CREATE = 60,
+		/// Drops a table
+		TABLE_DROP = 61,
+		/// The names of the tables of a database
+		TABLE_LIST = 62,
+		/// The configuration document of a database or table
+		CONFIG = 174,
+		/// The status document of a table
+		STATUS = 175,
+		/// Waits for a table or the tables of a database to be ready
+		WAIT = 177,
+		/// Changes the sharding and replication of a table
+		RECONFIGURE = 176,
+		/// Rebalances the shards of a table
+		REBALANCE = 179,
+		/// Flushes a table's soft durability writes to disk
+		SYNC = 138,
+		/// Grants or revokes a user's permissions
+		GRANT = 188,
+		/// Creates a secondary index
+		INDEX_CREATE = 75,
+		/// Drops a secondary index
+		INDEX_DROP = 76,
+		/// The names of the secondary indexes of a table
+		INDEX_LIST = 77,
+		/// The status of the secondary indexes of a table
+		INDEX_STATUS = 139,
+		/// Waits for secondary indexes to be ready
+		INDEX_WAIT = 140,
+		/// Renames a secondary index
+		INDEX_RENAME = 156,
+		/// Sets or removes the write hook of a table
+		SET_WRITE_HOOK = 189,
+		/// The write hook of a table
+		GET_WRITE_HOOK = 190,
+		
+		//Control structures
+		/// Calls a function with arguments
+		FUNCALL = 64,
+		/// One of two values depending on a condition
+		BRANCH = 65,
+		/// True if any argument is true, short-circuiting
+		OR = 66,
+		/// True if all arguments are true, short-circuiting
+		AND = 67,
+		/// Runs a write query for each element of a sequence
+		FOR_EACH = 68,
+		/// A function of an array of parameter numbers and a body
+		FUNC = 69,
+		/// Ascending order for ORDER_BY
+		ASC = 73,
+		/// Descending order for ORDER_BY
+		DESC = 74,
+		/// Information about a value
+		INFO = 79,
+		
+		//Strings
+		/// The match of a regular expression against a string, or null
+		MATCH = 97,
+		/// A string in upper case
+		UPCASE = 141,
+		/// A string in lower case
+		DOWNCASE = 142,
+		/// A uniform random sample of a sequence
+		SAMPLE = 81,
+		/// A value, or a fallback when it is null or missing
+		DEFAULT = 92,
+		/// A value parsed from a JSON string
+		JSON = 98,
+		/// A value serialized as a JSON string
+		TO_JSON_STRING = 172,
+		
+		//Times
+		/// A time parsed from an ISO 8601 string
+		ISO8601 = 99,
+		/// A time formatted as an ISO 8601 string
+		TO_ISO8601 = 100,
+		/// A time from seconds since the Unix epoch
+		EPOCH_TIME = 101,
+		/// A time as seconds since the Unix epoch
+		TO_EPOCH_TIME = 102,
+		/// The time at which the query started
+		NOW = 103,
+		/// A time in a different timezone
+		IN_TIMEZONE = 104,
+		/// True if a time is between two others
+		DURING = 105,
+		/// A time with its time of day removed
+		DATE = 106,
+		/// The seconds since midnight of a time
+		TIME_OF_DAY = 126,
+		/// The timezone of a time
+		TIMEZONE = 127,
+		/// The year of a time
+		YEAR = 128,
+		/// The month of a time
+		MONTH = 129,
+		/// The day of the month of a time
+		DAY = 130,
+		/// The day of the week of a time, from 1 for Monday
+		DAY_OF_WEEK = 131,
+		/// The day of the year of a time, from 1
+		DAY_OF_YEAR = 132,
+		/// The hour of a time
+		HOURS = 133,
+		/// The minute of a time
+		MINUTES = 134,
+		/// The seconds of a time, with fractions
+		SECONDS = 135,
+		/// A time from a date, an optional time of day and a timezone
+		TIME = 136,
+		/// The constant 1, for comparing with DAY_OF_WEEK
+		MONDAY = 107,
+		/// The constant 2
+		TUESDAY = 108,
+		/// The constant 3
+		WEDNESDAY = 109,
+		/// The constant 4
+		THURSDAY = 110,
+		/// The constant 5
+		FRIDAY = 111,
+		/// The constant 6
+		SATURDAY = 112,
+		/// The constant 7
+		SUNDAY = 113,
+		/// The constant 1, for comparing with MONTH
+		JANUARY = 114,
+		/// The constant 2
+		FEBRUARY = 115,
+		/// The constant 3
+		MARCH = 116,
+		/// The constant 4
+		APRIL = 117,
+		/// The constant 5
+		MAY = 118,
+		/// The constant 6
+		JUNE = 119,
+		/// The constant 7
+		JULY = 120,
+		/// The constant 8
+		AUGUST = 121,
+		/// The constant 9
+		SEPTEMBER = 122,
+		/// The constant 10
+		OCTOBER = 123,
+		/// The constant 11
+		NOVEMBER = 124,
+		/// The constant 12
+		DECEMBER = 125,
+		
+		//Grouping and miscellaneous
+		/// A value that replaces a field outright instead of being merged into it
+		LITERAL = 137,
+		/// A sequence split into groups by fields, functions or an index
+		GROUP = 144,
+		/// The sum of a sequence
+		SUM = 145,
+		/// The average of a sequence
+		AVG = 146,
+		/// The smallest element of a sequence
+		MIN = 147,
+		/// The largest element of a sequence
+		MAX = 148,
+		/// A string split on whitespace or a separator
+		SPLIT = 149,
+		/// Grouped data as an array of group and reduction objects
+		UNGROUP = 150,
+		/// A random number
+		RANDOM = 151,
+		/// A changefeed on a table, selection or document
+		CHANGES = 152,
+		/// Splices an array into the arguments of the enclosing term
+		ARGS = 154,
+		/// A binary value from base64 data
+		BINARY = 155,
+		
+		//Geospatial
+		/// A geometry from a GeoJSON object
+		GEOJSON = 157,
+		/// A geometry as a GeoJSON object
+		TO_GEOJSON = 158,
+		/// A point from a longitude and latitude
+		POINT = 159,
+		/// A line from two or more points
+		LINE = 160,
+		/// A polygon from three or more points
+		POLYGON = 161,
+		/// The distance between two geometries
+		DISTANCE = 162,
+		/// True if two geometries intersect, or the geometries of a sequence that do
+		INTERSECTS = 163,
+		/// True if a geometry contains another, or the geometries of a sequence that do
+		INCLUDES = 164,
+		/// A polygon or line approximating a circle
+		CIRCLE = 165,
+		/// The documents of a table whose geospatial index intersects a geometry
+		GET_INTERSECTING = 166,
+		/// A line converted to a polygon
+		FILL = 167,
+		/// The documents of a table nearest to a point, by a geospatial index
+		GET_NEAREST = 168,
+		/// A polygon with another polygon cut out of it
+		POLYGON_SUB = 171,
+		
+		//Range bounds
+		/// A value less than every other, for open range bounds
+		MINVAL = 180,
+		/// A value greater than every other, for open range bounds
+		MAXVAL = 181,
+		
+		//Bitwise operations
+		/// Bitwise and of integers
+		BIT_AND = 191,
+		/// Bitwise or of integers
+		BIT_OR = 192,
+		/// Bitwise exclusive or of integers
+		BIT_XOR = 193,
+		/// Bitwise complement of an integer
+		BIT_NOT = 194,
+		/// Arithmetic left shift of an integer
+		BIT_SAL = 195,
+		/// Arithmetic right shift of an integer
+		BIT_SAR = 196,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn test_from_u64() {
+		assert_eq!(TermType::from_u64(69), Some(TermType::FUNC));
+		assert_eq!(TermType::from_u64(196), Some(TermType::BIT_SAR));
+		assert_eq!(TermType::from_u64(46), None);
+		assert_eq!(ResponseType::from_u64(3), Some(ResponseType::SUCCESS_PARTIAL));
+		assert_eq!(ErrorType::from_u64(4200000), Some(ErrorType::OP_INDETERMINATE));
+	}
+}
@@ -0,0 +1,191 @@
+use rustc_serialize::json::{self, ToJson};
+use ql2::TermType;
+use super::{TreeNode, Value, Queryable, Stream, Selection, SingleSelection, ReQLGenState, Table, OptArgs, make_term};
+
+/// Whether a `between` bound includes the boundary value
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Bound {
+	Open,
+	Closed,
+}
+
+impl ToJson for Bound {
+	fn to_json(&self) -> json::Json {
+		match *self {
+			Bound::Open => "open",
+			Bound::Closed => "closed",
+		}.to_json()
+	}
+}
+
+//Get
+
+/// A single document looked up by primary key
+pub struct Get<'a, K>
+	where K: Value
+{
+	table: Table<'a>,
+	key: K,
+}
+
+impl<'a, K> Get<'a, K>
+	where K: Value
+{
+	pub fn new(table: Table<'a>, key: K) -> Get<'a, K> {
+		Get {table: table, key: key}
+	}
+}
+
+impl<'a, K> TreeNode for Get<'a, K>
+	where K: Value
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let table = self.table.get_reql_json(state);
+		let key = self.key.get_reql_json(state);
+		make_term(TermType::GET, vec![table, key], None)
+	}
+}
+
+impl<'a, K> Value for Get<'a, K> where K: Value {}
+
+impl<'a, K> SingleSelection for Get<'a, K> where K: Value {}
+
+impl<'a, K> Queryable for Get<'a, K> where K: Value {}
+
+value_ops!(['a, K: Value,] Get<'a, K>);
+
+//GetAll
+
+/// All documents matching any of a set of keys, by primary key or by secondary index
+pub struct GetAll<'a, K>
+	where K: Value
+{
+	table: Table<'a>,
+	keys: Vec<K>,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, K> GetAll<'a, K>
+	where K: Value
+{
+	pub fn new(table: Table<'a>, keys: Vec<K>) -> GetAll<'a, K> {
+		GetAll {table: table, keys: keys, optargs: OptArgs::new()}
+	}
+	
+	/// Look the keys up in a secondary index rather than by primary key
+	pub fn index(mut self, name: &str) -> GetAll<'a, K> {
+		self.optargs.set("index", name.to_owned());
+		self
+	}
+}
+
+impl<'a, K> TreeNode for GetAll<'a, K>
+	where K: Value
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let mut args = vec![self.table.get_reql_json(state)];
+		args.extend(self.keys.iter().map(|key| key.get_reql_json(state)));
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::GET_ALL, args, optargs)
+	}
+}
+
+impl<'a, K> Stream for GetAll<'a, K> where K: Value {}
+
+impl<'a, K> Selection for GetAll<'a, K> where K: Value {}
+
+impl<'a, K> Queryable for GetAll<'a, K> where K: Value {}
+
+//Between
+
+/// All documents with a key in a range, by primary key or by secondary index
+pub struct Between<'a, L, U>
+	where L: Value, U: Value
+{
+	table: Table<'a>,
+	lower: L,
+	upper: U,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, L, U> Between<'a, L, U>
+	where L: Value, U: Value
+{
+	pub fn new(table: Table<'a>, lower: L, upper: U) -> Between<'a, L, U> {
+		Between {table: table, lower: lower, upper: upper, optargs: OptArgs::new()}
+	}
+	
+	/// Compare against a secondary index rather than the primary key
+	pub fn index(mut self, name: &str) -> Between<'a, L, U> {
+		self.optargs.set("index", name.to_owned());
+		self
+	}
+	
+	/// Defaults to `Bound::Closed`
+	pub fn left_bound(mut self, bound: Bound) -> Between<'a, L, U> {
+		self.optargs.set("left_bound", bound);
+		self
+	}
+	
+	/// Defaults to `Bound::Open`
+	pub fn right_bound(mut self, bound: Bound) -> Between<'a, L, U> {
+		self.optargs.set("right_bound", bound);
+		self
+	}
+}
+
+impl<'a, L, U> TreeNode for Between<'a, L, U>
+	where L: Value, U: Value
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let table = self.table.get_reql_json(state);
+		let lower = self.lower.get_reql_json(state);
+		let upper = self.upper.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::BETWEEN, vec![table, lower, upper], optargs)
+	}
+}
+
+impl<'a, L, U> Stream for Between<'a, L, U> where L: Value, U: Value {}
+
+impl<'a, L, U> Selection for Between<'a, L, U> where L: Value, U: Value {}
+
+impl<'a, L, U> Queryable for Between<'a, L, U> where L: Value, U: Value {}
+
+//MinVal and MaxVal
+
+/// A value smaller than any other, for use as an unbounded `between` limit
+#[derive(Copy,Clone)]
+pub struct MinVal;
+
+impl TreeNode for MinVal {
+	fn get_reql_json(&self, _state: &mut ReQLGenState) -> json::Json {
+		make_term(TermType::MINVAL, vec![], None)
+	}
+}
+
+impl Value for MinVal {}
+
+value_ops!(MinVal);
+
+pub fn minval() -> MinVal {
+	MinVal
+}
+
+/// A value larger than any other, for use as an unbounded `between` limit
+#[derive(Copy,Clone)]
+pub struct MaxVal;
+
+impl TreeNode for MaxVal {
+	fn get_reql_json(&self, _state: &mut ReQLGenState) -> json::Json {
+		make_term(TermType::MAXVAL, vec![], None)
+	}
+}
+
+impl Value for MaxVal {}
+
+value_ops!(MaxVal);
+
+pub fn maxval() -> MaxVal {
+	MaxVal
+}
@@ -0,0 +1,380 @@
+use rustc_serialize::json::{self, ToJson};
+use ql2::TermType;
+use super::{TreeNode, Value, Queryable, Selection, SingleSelection, Stream, ReQLGenState, ClosureVar, Func, Table, OptArgs, Bound, make_term};
+
+//Map
+
+/// A function applied to each element of a sequence: the result of `map` or `concat_map`
+pub struct Map<S, P>
+	where S: Stream
+{
+	term_type: TermType,
+	source: S,
+	func: Func<P>,
+}
+
+impl<S, P, T> Map<S, P>
+	where S: Stream, P: Fn(ClosureVar) -> T, T: TreeNode
+{
+	pub fn new(term_type: TermType, source: S, func: P) -> Map<S, P> {
+		Map {term_type: term_type, source: source, func: Func::new(func)}
+	}
+}
+
+impl<S, P, T> TreeNode for Map<S, P>
+	where S: Stream, P: Fn(ClosureVar) -> T, T: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let func = self.func.get_reql_json(state);
+		make_term(self.term_type, vec![source, func], None)
+	}
+}
+
+impl<S, P, T> Stream for Map<S, P> where S: Stream, P: Fn(ClosureVar) -> T, T: TreeNode {}
+
+impl<S, P, T> Queryable for Map<S, P> where S: Stream, P: Fn(ClosureVar) -> T, T: TreeNode {}
+
+//OrderBy
+
+/// A sort key for `order_by`. A field name sorts ascending; use `r::asc` and `r::desc` to be explicit.
+#[derive(Debug,Clone,PartialEq)]
+pub enum OrderKey {
+	Field(String),
+	Asc(String),
+	Desc(String),
+}
+
+impl<'a> From<&'a str> for OrderKey {
+	fn from(name: &'a str) -> OrderKey {
+		OrderKey::Field(name.to_owned())
+	}
+}
+
+impl From<String> for OrderKey {
+	fn from(name: String) -> OrderKey {
+		OrderKey::Field(name)
+	}
+}
+
+impl TreeNode for OrderKey {
+	fn get_reql_json(&self, _state: &mut ReQLGenState) -> json::Json {
+		match *self {
+			OrderKey::Field(ref name) => name.to_json(),
+			OrderKey::Asc(ref name) => make_term(TermType::ASC, vec![name.to_json()], None),
+			OrderKey::Desc(ref name) => make_term(TermType::DESC, vec![name.to_json()], None),
+		}
+	}
+}
+
+pub fn asc(field: &str) -> OrderKey {
+	OrderKey::Asc(field.to_owned())
+}
+
+pub fn desc(field: &str) -> OrderKey {
+	OrderKey::Desc(field.to_owned())
+}
+
+/// A sorted sequence. Ordering a selection keeps it a selection, so it can still be updated.
+pub struct OrderBy<'a, S>
+	where S: Stream
+{
+	source: S,
+	keys: Vec<OrderKey>,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S> OrderBy<'a, S>
+	where S: Stream
+{
+	pub fn new<I>(source: S, keys: I) -> OrderBy<'a, S>
+		where I: IntoIterator, I::Item: Into<OrderKey>
+	{
+		OrderBy {source: source, keys: keys.into_iter().map(Into::into).collect(), optargs: OptArgs::new()}
+	}
+}
+
+impl<'a, 'b> OrderBy<'a, Table<'b>> {
+	/// Sort a table by a secondary index, which avoids loading the whole table into memory and
+	/// is required for `order_by().limit().changes()`. The keys may then be empty.
+	pub fn index<K: Into<OrderKey>>(mut self, key: K) -> OrderBy<'a, Table<'b>> {
+		self.optargs.set("index", key.into());
+		self
+	}
+}
+
+impl<'a, S> TreeNode for OrderBy<'a, S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let mut args = vec![self.source.get_reql_json(state)];
+		args.extend(self.keys.iter().map(|k| k.get_reql_json(state)));
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::ORDER_BY, args, optargs)
+	}
+}
+
+impl<'a, S> Stream for OrderBy<'a, S> where S: Stream {}
+
+impl<'a, S> Selection for OrderBy<'a, S> where S: Selection {}
+
+impl<'a, S> Queryable for OrderBy<'a, S> where S: Stream {}
+
+//Limit
+
+/// A sequence cut down to a number of elements: the result of `limit`, `skip` or `sample`
+pub struct Limit<S>
+	where S: Stream
+{
+	term_type: TermType,
+	source: S,
+	n: u64,
+}
+
+impl<S> Limit<S>
+	where S: Stream
+{
+	pub fn new(term_type: TermType, source: S, n: u64) -> Limit<S> {
+		Limit {term_type: term_type, source: source, n: n}
+	}
+}
+
+impl<S> TreeNode for Limit<S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		make_term(self.term_type, vec![source, self.n.to_json()], None)
+	}
+}
+
+impl<S> Stream for Limit<S> where S: Stream {}
+
+impl<S> Selection for Limit<S> where S: Selection {}
+
+impl<S> Queryable for Limit<S> where S: Stream {}
+
+//Slice
+
+/// The elements of a sequence between two positions
+pub struct Slice<'a, S>
+	where S: Stream
+{
+	source: S,
+	start: i64,
+	end: Option<i64>,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S> Slice<'a, S>
+	where S: Stream
+{
+	pub fn new(source: S, start: i64, end: Option<i64>) -> Slice<'a, S> {
+		Slice {source: source, start: start, end: end, optargs: OptArgs::new()}
+	}
+	
+	/// Defaults to `Bound::Closed`
+	pub fn left_bound(mut self, bound: Bound) -> Slice<'a, S> {
+		self.optargs.set("left_bound", bound);
+		self
+	}
+	
+	/// Defaults to `Bound::Open`
+	pub fn right_bound(mut self, bound: Bound) -> Slice<'a, S> {
+		self.optargs.set("right_bound", bound);
+		self
+	}
+}
+
+impl<'a, S> TreeNode for Slice<'a, S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let mut args = vec![self.source.get_reql_json(state), self.start.to_json()];
+		if let Some(end) = self.end {
+			args.push(end.to_json());
+		}
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::SLICE, args, optargs)
+	}
+}
+
+impl<'a, S> Stream for Slice<'a, S> where S: Stream {}
+
+impl<'a, S> Selection for Slice<'a, S> where S: Selection {}
+
+impl<'a, S> Queryable for Slice<'a, S> where S: Stream {}
+
+//Nth
+
+/// A single element of a sequence. The element of a selection can still be updated.
+pub struct Nth<S>
+	where S: Stream
+{
+	source: S,
+	index: i64,
+}
+
+impl<S> Nth<S>
+	where S: Stream
+{
+	pub fn new(source: S, index: i64) -> Nth<S> {
+		Nth {source: source, index: index}
+	}
+}
+
+impl<S> TreeNode for Nth<S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		make_term(TermType::NTH, vec![source, self.index.to_json()], None)
+	}
+}
+
+impl<S> Value for Nth<S> where S: Stream {}
+
+impl<S> SingleSelection for Nth<S> where S: Selection {}
+
+impl<S> Queryable for Nth<S> where S: Stream {}
+
+value_ops!([S: Stream,] Nth<S>);
+
+//IsEmpty
+
+/// True if a sequence has no elements
+pub struct IsEmpty<S>
+	where S: Stream
+{
+	source: S,
+}
+
+impl<S> IsEmpty<S>
+	where S: Stream
+{
+	pub fn new(source: S) -> IsEmpty<S> {
+		IsEmpty {source: source}
+	}
+}
+
+impl<S> TreeNode for IsEmpty<S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		make_term(TermType::IS_EMPTY, vec![source], None)
+	}
+}
+
+impl<S> Value for IsEmpty<S> where S: Stream {}
+
+impl<S> Queryable for IsEmpty<S> where S: Stream {}
+
+value_ops!([S: Stream,] IsEmpty<S>);
+
+//Union
+
+/// The elements of two sequences. By default the elements are interleaved in whatever order
+/// they become available.
+pub struct Union<'a, S, T>
+	where S: Stream, T: Stream
+{
+	source: S,
+	other: T,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S, T> Union<'a, S, T>
+	where S: Stream, T: Stream
+{
+	pub fn new(source: S, other: T) -> Union<'a, S, T> {
+		Union {source: source, other: other, optargs: OptArgs::new()}
+	}
+	
+	/// If false, all of the elements of the first sequence are returned before any of the second
+	pub fn interleave(mut self, val: bool) -> Union<'a, S, T> {
+		self.optargs.set("interleave", val);
+		self
+	}
+	
+	/// Merges two sequences that are already sorted by `key` so that the result stays sorted
+	pub fn interleave_by<K: Into<OrderKey>>(mut self, key: K) -> Union<'a, S, T> {
+		self.optargs.set("interleave", key.into());
+		self
+	}
+}
+
+impl<'a, S, T> TreeNode for Union<'a, S, T>
+	where S: Stream, T: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let other = self.other.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::UNION, vec![source, other], optargs)
+	}
+}
+
+impl<'a, S, T> Stream for Union<'a, S, T> where S: Stream, T: Stream {}
+
+impl<'a, S, T> Queryable for Union<'a, S, T> where S: Stream, T: Stream {}
+
+//Distinct
+
+/// The elements of a sequence with duplicates removed
+pub struct Distinct<S>
+	where S: Stream
+{
+	source: S,
+}
+
+impl<S> Distinct<S>
+	where S: Stream
+{
+	pub fn new(source: S) -> Distinct<S> {
+		Distinct {source: source}
+	}
+}
+
+impl<S> TreeNode for Distinct<S>
+	where S: Stream
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		make_term(TermType::DISTINCT, vec![source], None)
+	}
+}
+
+impl<S> Stream for Distinct<S> where S: Stream {}
+
+impl<S> Queryable for Distinct<S> where S: Stream {}
+
+//AsStream
+
+/// A value used as a sequence, created by `Value::as_stream`. The server rejects the query if the
+/// value is not an array or other sequence.
+pub struct AsStream<V>
+	where V: Value
+{
+	value: V,
+}
+
+impl<V> AsStream<V>
+	where V: Value
+{
+	pub fn new(value: V) -> AsStream<V> {
+		AsStream {value: value}
+	}
+}
+
+impl<V> TreeNode for AsStream<V>
+	where V: Value
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		self.value.get_reql_json(state)
+	}
+}
+
+impl<V> Stream for AsStream<V> where V: Value {}
+
+impl<V> Queryable for AsStream<V> where V: Value {}
@@ -0,0 +1,493 @@
+use rustc_serialize::json::{self, ToJson};
+use serde::de::DeserializeOwned;
+use std::result::Result;
+use err::{QueryError, ConnectionError, DataError};
+use ql2::TermType;
+use super::{TreeNode, Value, Queryable, ReQLGenState, ClosureVar, Func3, Table, OptArgs, make_term};
+use changes::{ChangeType, FeedState};
+use net::{Connection, Transport};
+use convert;
+
+/// How `insert` resolves a document whose primary key already exists
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Conflict {
+	/// Do not insert the new document and record the conflict as an error
+	Error,
+	/// Replace the old document with the new one
+	Replace,
+	/// Update the old document with the fields of the new one
+	Update,
+}
+
+impl ToJson for Conflict {
+	fn to_json(&self) -> json::Json {
+		match *self {
+			Conflict::Error => "error",
+			Conflict::Replace => "replace",
+			Conflict::Update => "update",
+		}.to_json()
+	}
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Durability {
+	/// The write is acknowledged once it has been committed to disk
+	Hard,
+	/// The write is acknowledged once it has been stored in memory
+	Soft,
+}
+
+impl ToJson for Durability {
+	fn to_json(&self) -> json::Json {
+		match *self {
+			Durability::Hard => "hard",
+			Durability::Soft => "soft",
+		}.to_json()
+	}
+}
+
+//optargs shared by all write terms
+macro_rules! write_optargs {
+	() => {
+		/// Whether the write is acknowledged before or after it reaches disk, overriding the table's setting
+		pub fn durability(mut self, val: Durability) -> Self {
+			self.optargs.set("durability", val);
+			self
+		}
+		
+		/// Include the old and new values of each changed document in the `changes` field of the result
+		pub fn return_changes(mut self, val: bool) -> Self {
+			self.optargs.set("return_changes", val);
+			self
+		}
+		
+		/// Include an entry in `changes` for every document, even those that were not modified
+		pub fn return_changes_always(mut self) -> Self {
+			self.optargs.set("return_changes", String::from("always"));
+			self
+		}
+		
+		/// Bypass the table's write hook (requires the `config` permission)
+		pub fn ignore_write_hook(mut self, val: bool) -> Self {
+			self.optargs.set("ignore_write_hook", val);
+			self
+		}
+	}
+}
+
+//Insert
+
+pub struct Insert<'a, T>
+	where T: Value
+{
+	table: Table<'a>,
+	docs: T,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, T> Insert<'a, T>
+	where T: Value
+{
+	pub fn new(table: Table<'a>, docs: T) -> Insert<'a, T> {
+		Insert {table: table, docs: docs, optargs: OptArgs::new()}
+	}
+	
+	pub fn conflict(mut self, val: Conflict) -> Insert<'a, T> {
+		self.optargs.set("conflict", val);
+		self
+	}
+	
+	/// Resolves conflicts with a function of the primary key, the old document and the new document
+	pub fn conflict_fn<F, R>(mut self, func: F) -> Insert<'a, T>
+		where
+			F: 'a + Fn(ClosureVar, ClosureVar, ClosureVar) -> R,
+			R: TreeNode
+	{
+		self.optargs.set("conflict", Func3::new(func));
+		self
+	}
+	
+	write_optargs!();
+}
+
+impl<'a, T> TreeNode for Insert<'a, T>
+	where T: Value
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let table = self.table.get_reql_json(state);
+		let docs = self.docs.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::INSERT, vec![table, docs], optargs)
+	}
+}
+
+impl<'a, T> Queryable for Insert<'a, T> where T: Value {}
+
+impl<'a, T> WriteQuery for Insert<'a, T> where T: Value {}
+
+//Update
+
+pub struct Update<'a, S, T>
+	where S: TreeNode, T: TreeNode
+{
+	source: S,
+	doc: T,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S, T> Update<'a, S, T>
+	where S: TreeNode, T: TreeNode
+{
+	pub fn new(source: S, doc: T) -> Update<'a, S, T> {
+		Update {source: source, doc: doc, optargs: OptArgs::new()}
+	}
+	
+	/// Allow the update function to contain non-deterministic operations
+	pub fn non_atomic(mut self, val: bool) -> Update<'a, S, T> {
+		self.optargs.set("non_atomic", val);
+		self
+	}
+	
+	write_optargs!();
+}
+
+impl<'a, S, T> TreeNode for Update<'a, S, T>
+	where S: TreeNode, T: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let doc = self.doc.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::UPDATE, vec![source, doc], optargs)
+	}
+}
+
+impl<'a, S, T> Queryable for Update<'a, S, T> where S: TreeNode, T: TreeNode {}
+
+impl<'a, S, T> WriteQuery for Update<'a, S, T> where S: TreeNode, T: TreeNode {}
+
+//Replace
+
+pub struct Replace<'a, S, T>
+	where S: TreeNode, T: TreeNode
+{
+	source: S,
+	doc: T,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S, T> Replace<'a, S, T>
+	where S: TreeNode, T: TreeNode
+{
+	pub fn new(source: S, doc: T) -> Replace<'a, S, T> {
+		Replace {source: source, doc: doc, optargs: OptArgs::new()}
+	}
+	
+	/// Allow the replacement function to contain non-deterministic operations
+	pub fn non_atomic(mut self, val: bool) -> Replace<'a, S, T> {
+		self.optargs.set("non_atomic", val);
+		self
+	}
+	
+	write_optargs!();
+}
+
+impl<'a, S, T> TreeNode for Replace<'a, S, T>
+	where S: TreeNode, T: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let doc = self.doc.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::REPLACE, vec![source, doc], optargs)
+	}
+}
+
+impl<'a, S, T> Queryable for Replace<'a, S, T> where S: TreeNode, T: TreeNode {}
+
+impl<'a, S, T> WriteQuery for Replace<'a, S, T> where S: TreeNode, T: TreeNode {}
+
+//Delete
+
+pub struct Delete<'a, S>
+	where S: TreeNode
+{
+	source: S,
+	optargs: OptArgs<'a>,
+}
+
+impl<'a, S> Delete<'a, S>
+	where S: TreeNode
+{
+	pub fn new(source: S) -> Delete<'a, S> {
+		Delete {source: source, optargs: OptArgs::new()}
+	}
+	
+	write_optargs!();
+}
+
+impl<'a, S> TreeNode for Delete<'a, S>
+	where S: TreeNode
+{
+	fn get_reql_json(&self, state: &mut ReQLGenState) -> json::Json {
+		let source = self.source.get_reql_json(state);
+		let optargs = self.optargs.get_reql_json(state);
+		make_term(TermType::DELETE, vec![source], optargs)
+	}
+}
+
+impl<'a, S> Queryable for Delete<'a, S> where S: TreeNode {}
+
+impl<'a, S> WriteQuery for Delete<'a, S> where S: TreeNode {}
+
+//Results
+
+/// The old and new values of a document, as returned by a write run with `return_changes`
+/// or by a changefeed
+#[derive(Debug,Clone,PartialEq)]
+pub struct Change<T = json::Json> {
+	pub old_val: Option<T>,
+	pub new_val: Option<T>,
+	/// Only sent by changefeeds run with `include_types`
+	pub change_type: Option<ChangeType>,
+	/// Set instead of the values for the status events sent by changefeeds run with `include_states`
+	pub state: Option<FeedState>,
+	/// The positions of the document in an `order_by().limit()` changefeed run with `include_offsets`
+	pub old_offset: Option<u64>,
+	pub new_offset: Option<u64>,
+}
+
+impl Change {
+	pub fn from_json(obj: &json::Json) -> Result<Change, DataError> {
+		Change::parse(obj, &|doc: &json::Json| Ok(doc.clone()))
+	}
+}
+
+impl<T: DeserializeOwned> Change<T> {
+	/// Parses the change, deserializing the old and new values into `T`
+	pub fn decode(obj: &json::Json) -> Result<Change<T>, DataError> {
+		Change::parse(obj, &|doc: &json::Json| convert::from_json(doc.clone()))
+	}
+}
+
+impl<T> Change<T> {
+	fn parse<F>(obj: &json::Json, decode_doc: &F) -> Result<Change<T>, DataError>
+		where F: Fn(&json::Json) -> Result<T, DataError>
+	{
+		let malformed = || DataError::MalformedPacket(obj.clone());
+		
+		if !obj.is_object() {
+			return Err(malformed());
+		}
+		
+		let doc = |name: &str| match obj.find(name) {
+			Some(&json::Json::Null) | None => Ok(None),
+			Some(val) => decode_doc(val).map(Some),
+		};
+		
+		let offset = |name: &str| match obj.find(name) {
+			Some(&json::Json::Null) | None => Ok(None),
+			Some(val) => val.as_u64().map(Some).ok_or_else(&malformed),
+		};
+		
+		let change_type = match obj.find("type") {
+			Some(&json::Json::String(ref t)) => Some(try!(ChangeType::from_name(t).ok_or_else(&malformed))),
+			None => None,
+			_ => return Err(malformed()),
+		};
+		
+		let state = match obj.find("state") {
+			Some(&json::Json::String(ref s)) => Some(try!(FeedState::from_name(s).ok_or_else(&malformed))),
+			None => None,
+			_ => return Err(malformed()),
+		};
+		
+		Ok(Change {
+			old_val: try!(doc("old_val")),
+			new_val: try!(doc("new_val")),
+			change_type: change_type,
+			state: state,
+			old_offset: try!(offset("old_offset")),
+			new_offset: try!(offset("new_offset")),
+		})
+	}
+}
+
+/// The summary returned by the server for `insert`, `update`, `replace` and `delete`.
+/// The documents in `changes` are raw JSON unless decoded with `WriteResult::decode`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct WriteResult<T = json::Json> {
+	pub inserted: u64,
+	pub replaced: u64,
+	pub unchanged: u64,
+	pub skipped: u64,
+	pub deleted: u64,
+	pub errors: u64,
+	/// The first error encountered, if `errors` is non-zero
+	pub first_error: Option<String>,
+	/// The primary keys generated for inserted documents that did not specify one
+	pub generated_keys: Vec<json::Json>,
+	/// Only populated if the write was run with `return_changes`
+	pub changes: Vec<Change<T>>,
+	pub warnings: Vec<String>,
+}
+
+impl<T> Default for WriteResult<T> {
+	fn default() -> WriteResult<T> {
+		WriteResult {
+			inserted: 0,
+			replaced: 0,
+			unchanged: 0,
+			skipped: 0,
+			deleted: 0,
+			errors: 0,
+			first_error: None,
+			generated_keys: Vec::new(),
+			changes: Vec::new(),
+			warnings: Vec::new(),
+		}
+	}
+}
+
+impl WriteResult {
+	pub fn from_json(obj: &json::Json) -> Result<WriteResult, DataError> {
+		WriteResult::parse(obj, |doc| Ok(doc.clone()))
+	}
+}
+
+impl<T: DeserializeOwned> WriteResult<T> {
+	/// Parses the summary, deserializing the documents in `changes` into `T`
+	pub fn decode(obj: &json::Json) -> Result<WriteResult<T>, DataError> {
+		WriteResult::parse(obj, |doc| convert::from_json(doc.clone()))
+	}
+}
+
+impl<T> WriteResult<T> {
+	fn parse<F>(obj: &json::Json, decode_doc: F) -> Result<WriteResult<T>, DataError>
+		where F: Fn(&json::Json) -> Result<T, DataError>
+	{
+		let malformed = || DataError::MalformedPacket(obj.clone());
+		
+		if !obj.is_object() {
+			return Err(malformed());
+		}
+		
+		let count = |field: &str| match obj.find(field) {
+			Some(val) => val.as_u64().ok_or_else(&malformed),
+			None => Ok(0),
+		};
+		
+		let first_error = match obj.find("first_error") {
+			Some(&json::Json::String(ref s)) => Some(s.clone()),
+			None => None,
+			_ => return Err(malformed()),
+		};
+		
+		let generated_keys = match obj.find("generated_keys") {
+			Some(&json::Json::Array(ref keys)) => keys.clone(),
+			None => Vec::new(),
+			_ => return Err(malformed()),
+		};
+		
+		let changes = match obj.find("changes") {
+			Some(&json::Json::Array(ref changes)) => try!(changes.iter()
+				.map(|change| Change::parse(change, &decode_doc))
+				.collect::<Result<Vec<_>, DataError>>()),
+			None => Vec::new(),
+			_ => return Err(malformed()),
+		};
+		
+		let warnings = match obj.find("warnings") {
+			Some(&json::Json::Array(ref warnings)) => try!(warnings.iter()
+				.map(|w| w.as_string().map(|s| s.to_owned()))
+				.collect::<Option<Vec<_>>>()
+				.ok_or_else(&malformed)),
+			None => Vec::new(),
+			_ => return Err(malformed()),
+		};
+		
+		Ok(WriteResult {
+			inserted: try!(count("inserted")),
+			replaced: try!(count("replaced")),
+			unchanged: try!(count("unchanged")),
+			skipped: try!(count("skipped")),
+			deleted: try!(count("deleted")),
+			errors: try!(count("errors")),
+			first_error: first_error,
+			generated_keys: generated_keys,
+			changes: changes,
+			warnings: warnings,
+		})
+	}
+}
+
+pub trait WriteQuery : Queryable {
+	/// Runs the write and decodes the server's summary
+	fn run_write<S: Transport>(self, conn: &Connection<S>) -> Result<WriteResult, QueryError>
+		where Self: Sized
+	{
+		let obj = try!(run_for_summary(self, conn));
+		WriteResult::from_json(&obj).map_err(|e| QueryError::from(ConnectionError::Data(e)))
+	}
+	
+	/// Runs the write, deserializing the documents returned by `return_changes` into `T`
+	fn run_write_as<T: DeserializeOwned, S: Transport>(self, conn: &Connection<S>) -> Result<WriteResult<T>, QueryError>
+		where Self: Sized
+	{
+		let obj = try!(run_for_summary(self, conn));
+		WriteResult::decode(&obj).map_err(|e| QueryError::from(ConnectionError::Data(e)))
+	}
+}
+
+fn run_for_summary<Q: Queryable, S: Transport>(query: Q, conn: &Connection<S>) -> Result<json::Json, QueryError> {
+	let mut result = try!(query.run(conn));
+	
+	match result.next() {
+		Some(result) => result,
+		None => Err(QueryError::from(ConnectionError::Data(DataError::NoDataReceived))),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rustc_serialize::json::Json;
+	use super::*;
+	
+	#[test]
+	fn test_write_result() {
+		let obj = Json::from_str("{
+			\"inserted\": 2, \"replaced\": 0, \"unchanged\": 0, \"skipped\": 0, \"deleted\": 0, \"errors\": 1,
+			\"first_error\": \"Duplicate primary key `id`\",
+			\"generated_keys\": [\"a4b1d0e6-3f1e-4c8c-9d4a-1c2b3d4e5f60\"],
+			\"changes\": [{\"old_val\": null, \"new_val\": {\"id\": 1}}]
+		}").unwrap();
+		
+		let result = WriteResult::from_json(&obj).unwrap();
+		
+		assert_eq!(result.inserted, 2);
+		assert_eq!(result.errors, 1);
+		assert_eq!(result.first_error, Some(String::from("Duplicate primary key `id`")));
+		assert_eq!(result.generated_keys.len(), 1);
+		assert_eq!(result.changes[0].old_val, None);
+		assert!(result.changes[0].new_val.is_some());
+		assert!(result.warnings.is_empty());
+	}
+	
+	#[test]
+	fn test_change_event() {
+		let obj = Json::from_str("{\"new_val\": {\"id\": 1}, \"type\": \"initial\", \"new_offset\": 0}").unwrap();
+		let change = Change::from_json(&obj).unwrap();
+		
+		assert_eq!(change.old_val, None);
+		assert_eq!(change.new_val, Some(Json::from_str("{\"id\": 1}").unwrap()));
+		assert_eq!(change.change_type, Some(ChangeType::Initial));
+		assert_eq!(change.state, None);
+		assert_eq!(change.new_offset, Some(0));
+		
+		let obj = Json::from_str("{\"state\": \"ready\", \"type\": \"state\"}").unwrap();
+		let change = Change::from_json(&obj).unwrap();
+		
+		assert_eq!(change.state, Some(FeedState::Ready));
+		assert!(Change::from_json(&Json::from_str("{\"type\": \"bogus\"}").unwrap()).is_err());
+	}
+}
@@ -1,9 +1,14 @@
 extern crate recogitate;
 extern crate rustc_serialize;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
 
 use rustc_serialize::json::ToJson;
 use recogitate as r;
 use recogitate::prelude::*;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 #[test]
 fn test_run_query() {
@@ -14,6 +19,55 @@ fn test_run_query() {
 	//panic!();
 }
 
+#[test]
+fn test_cursor_close() {
+	let mut conn = r::connection().connect().unwrap();
+	
+	{
+		let mut result_cursor = r::db("task_planner").table("tasks").run(&mut conn).unwrap();
+		result_cursor.next();
+		result_cursor.close().unwrap();
+		assert!(result_cursor.next().is_none());
+	}
+	
+	//the connection is still usable after a STOP
+	let all = r::db("task_planner").table("tasks").run(&mut conn).unwrap().collect::<Result<Vec<_>, _>>();
+	assert!(all.is_ok());
+}
+
+#[test]
+fn test_multiplexed_queries() {
+	let conn = Arc::new(r::connection().dbname(String::from("task_planner")).connect().unwrap());
+	
+	//two cursors open at once, read alternately
+	{
+		let mut a = r::table("tasks").run(&*conn).unwrap();
+		let mut b = r::table("tasks").run(&*conn).unwrap();
+		let (mut count_a, mut count_b) = (0, 0);
+		
+		loop {
+			let (next_a, next_b) = (a.next(), b.next());
+			if next_a.is_none() && next_b.is_none() {
+				break;
+			}
+			if next_a.is_some() { count_a += 1; }
+			if next_b.is_some() { count_b += 1; }
+		}
+		assert_eq!(count_a, count_b);
+	}
+	
+	//several threads sharing the connection
+	let workers = (0..4).map(|_| {
+		let conn = conn.clone();
+		thread::spawn(move || {
+			r::table("tasks").run(&*conn).unwrap().collect::<Result<Vec<_>, _>>().unwrap().len()
+		})
+	}).collect::<Vec<_>>();
+	
+	let counts = workers.into_iter().map(|w| w.join().unwrap()).collect::<Vec<_>>();
+	assert!(counts.iter().all(|&c| c == counts[0]));
+}
+
 #[test]
 fn test_query_json() {
 	let mut state = r::ReQLGenState::new();
@@ -36,4 +90,454 @@ fn test_query_json() {
 	
 	println!("{}", json_output);
 	//panic!();
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_expr_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::expr(&vec![vec![1, 2], vec![3]]).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[2,[[2,[1,2]],[2,[3]]]]");
+	
+	let json_output = r::object()
+		.insert("tags", r::expr(&vec!["a".to_owned(), "b".to_owned()]))
+		.insert("users", r::db("blog").table("users"))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "{\"tags\":[2,[\"a\",\"b\"]],\"users\":[15,[[14,[\"blog\"]],\"users\"]]}");
+	
+	let json_output = r::array().push(1).push(r::expr("x")).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[2,[1,\"x\"]]");
+	
+	let task = Task {id: String::from("a"), title: String::from("Expr"), done: true};
+	let json_output = r::expr(&task).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "{\"done\":true,\"id\":\"a\",\"title\":\"Expr\"}");
+	assert_eq!(r::try_expr(&task).unwrap().get_reql_json(&mut state), json_output);
+	
+	let json_output = r::expr_json(&rustc_serialize::json::Json::from_str("{\"a\": [1]}").unwrap()).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "{\"a\":[2,[1]]}");
+	
+	//JSON objects can only have string keys
+	let mut by_pair = std::collections::BTreeMap::new();
+	by_pair.insert((1, 2), "x");
+	assert!(r::try_expr(&by_pair).is_err());
+}
+
+#[test]
+#[should_panic]
+fn test_expr_unrepresentable() {
+	let mut by_pair = std::collections::BTreeMap::new();
+	by_pair.insert((1, 2), "x");
+	r::expr(&by_pair);
+}
+
+#[test]
+fn test_operators_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::expr(&5).add(3).mul(2).ge(16).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[22,[[26,[[24,[5,3]],2]],16]]");
+	
+	let json_output = ((r::expr(&5) + 3) * 2 % 4).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[28,[[26,[[24,[5,3]],2]],4]]");
+	
+	let json_output = (!r::expr(&true) | r::expr(&1.5).round().lt(2)).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[66,[[23,[true]],[19,[[185,[1.5]],2]]]]");
+	
+	let json_output = r::table("users")
+		.update_fn(|doc| r::object().insert("visits", doc.ne(r::expr(&0)) & doc.floor()))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[53,[[15,[\"users\"]],[69,[[2,[0]],{\"visits\":[67,[[18,[[10,[0]],0]],[183,[[10,[0]]]]]]}]]]]");
+	
+	let json_output = r::table("users")
+		.reduce(|a, b| r::branch(a.get_field("age").gt(b.get_field("age")), a, b))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[37,[[15,[\"users\"]],[69,[[2,[1,2]],[65,[[21,[[31,[[10,[1]],\"age\"]],[31,[[10,[2]],\"age\"]]]],[10,[1]],[10,[2]]]]]]]]");
+	
+	let json_output = r::expr(&1).eq(2).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[17,[1,2]]");
+	
+	//every term that is a single value supports the operators
+	let json_output = (r::table("t").nth(0) + 1).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[24,[[45,[[15,[\"t\"]],0]],1]]");
+	
+	let json_output = (r::table("t").count() / 2).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[27,[[43,[[15,[\"t\"]]]],2]]");
+	
+	let json_output = (r::table("t").max("age") - r::table("t").get(r::expr("a")).get_field("age")).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[25,[[148,[[15,[\"t\"]],\"age\"]],[31,[[16,[[15,[\"t\"]],\"a\"]],\"age\"]]]]");
+	
+	let json_output = (r::array().push(1) * 2 + r::object().insert("a", 1).merge(r::object())).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[24,[[26,[[2,[1]],2]],[35,[{\"a\":1},{}]]]]");
+}
+
+#[test]
+fn test_document_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::table("users")
+		.pluck(vec![r::Selector::from("name"), r::nested("address", vec!["city", "country"])])
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[33,[[15,[\"users\"]],\"name\",{\"address\":[2,[\"city\",\"country\"]]}]]");
+	
+	let json_output = r::table("users").get(r::expr("ann")).without(vec!["password"]).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[34,[[16,[[15,[\"users\"]],\"ann\"]],\"password\"]]");
+	
+	let json_output = r::table("users")
+		.merge_fn(|user| r::object().insert("adult", user.get_field("age").ge(18)))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[35,[[15,[\"users\"]],[69,[[2,[0]],{\"adult\":[22,[[31,[[10,[0]],\"age\"]],18]]}]]]]");
+	
+	let json_output = r::table("users")
+		.get(r::expr("ann"))
+		.update(r::object().insert("address", r::literal(r::object().insert("city", r::expr("Leeds")))))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[53,[[16,[[15,[\"users\"]],\"ann\"]],{\"address\":[137,[{\"city\":\"Leeds\"}]]}]]");
+	
+	let json_output = r::literal_remove().get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[137,[]]");
+	
+	let json_output = r::table("users")
+		.get(r::expr("ann"))
+		.update(r::object().insert("password", r::literal_remove()))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[53,[[16,[[15,[\"users\"]],\"ann\"]],{\"password\":[137,[]]}]]");
+	
+	let json_output = r::expr(&vec![1, 2]).bracket(0).add(r::object().insert("a", 1).keys().bracket(0)).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[24,[[170,[[2,[1,2]],0]],[170,[[94,[{\"a\":1}]],0]]]]");
+}
+
+#[test]
+fn test_sequence_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::table("users").filter_fn(|user| user.get_field("age").gt(18)).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[39,[[15,[\"users\"]],[69,[[2,[0]],[21,[[31,[[10,[0]],\"age\"]],18]]]]]]");
+	
+	//ordering and limiting a selection keeps it updatable
+	let json_output = r::table("users")
+		.order_by(vec![r::desc("score"), r::OrderKey::from("name")])
+		.skip(10)
+		.limit(5)
+		.update(r::object().insert("featured", true))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[53,[[71,[[70,[[41,[[15,[\"users\"]],[74,[\"score\"]],\"name\"]],10]],5]],{\"featured\":true}]]");
+	
+	let json_output = r::table("scores")
+		.order_by(Vec::<r::OrderKey>::new())
+		.index(r::desc("score"))
+		.limit(3)
+		.changes()
+		.include_offsets(true)
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[152,[[71,[[41,[[15,[\"scores\"]]],{\"index\":[74,[\"score\"]]}],3]]],{\"include_offsets\":true}]");
+	
+	let json_output = r::table("users")
+		.slice(2, Some(4))
+		.right_bound(r::Bound::Closed)
+		.nth(-1)
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[45,[[30,[[15,[\"users\"]],2,4],{\"right_bound\":\"closed\"}],-1]]");
+	
+	let json_output = r::table("users")
+		.map(|user| user.get_field("tags"))
+		.union(r::table("posts").concat_map(|post| post.get_field("tags")))
+		.interleave(false)
+		.distinct()
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[42,[[44,[[38,[[15,[\"users\"]],[69,[[2,[1]],[31,[[10,[1]],\"tags\"]]]]]],[40,[[15,[\"posts\"]],[69,[[2,[2]],[31,[[10,[2]],\"tags\"]]]]]]],{\"interleave\":false}]]]");
+	
+	//arrays and fields holding arrays can be used as sequences
+	let json_output = r::array().push(3).push(r::expr(&1)).as_stream().union(r::expr(&vec![2]).as_stream()).nth(0).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[45,[[44,[[2,[3,1]],[2,[2]]]],0]]");
+	
+	let json_output = r::table("posts").map(|post| post.get_field("tags").as_stream().distinct().limit(2)).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[38,[[15,[\"posts\"]],[69,[[2,[3]],[71,[[42,[[31,[[10,[3]],\"tags\"]]]],2]]]]]]");
+	
+	let json_output = (!r::table("users").sample(1).is_empty()).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[23,[[86,[[81,[[15,[\"users\"]],1]]]]]]");
+}
+
+#[test]
+fn test_aggregate_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::table("tasks").group(vec!["done"]).count().ungroup().get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[150,[[43,[[144,[[15,[\"tasks\"]],\"done\"]]]]]]");
+	
+	let json_output = r::table("users").group_index("country").multi(true).avg("age").get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[146,[[144,[[15,[\"users\"]]],{\"index\":\"country\",\"multi\":true}],\"age\"]]");
+	
+	let json_output = r::table("users").group(vec![r::Selector::from("team"), r::nested("address", vec!["country"])]).count().get_reql_json(&mut r::ReQLGenState::new());
+	assert_eq!(format!("{}", json_output), "[43,[[144,[[15,[\"users\"]],\"team\",[69,[[2,[0]],[31,[[31,[[10,[0]],\"address\"]],\"country\"]]]]]]]]");
+	
+	let json_output = r::table("users").map(|user| user.get_field("age")).count_value(30).get_reql_json(&mut r::ReQLGenState::new());
+	assert_eq!(format!("{}", json_output), "[43,[[38,[[15,[\"users\"]],[69,[[2,[0]],[31,[[10,[0]],\"age\"]]]]]],30]]");
+	
+	let json_output = r::table("users").count_fn(|user| user.get_field("age").ge(18)).get_reql_json(&mut r::ReQLGenState::new());
+	assert_eq!(format!("{}", json_output), "[43,[[15,[\"users\"]],[69,[[2,[0]],[22,[[31,[[10,[0]],\"age\"]],18]]]]]]");
+	
+	let json_output = r::table("users").map(|user| user.get_field("age")).reduce(|a, b| a + b).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[37,[[38,[[15,[\"users\"]],[69,[[2,[0]],[31,[[10,[0]],\"age\"]]]]]],[69,[[2,[1,2]],[24,[[10,[1]],[10,[2]]]]]]]]");
+	
+	let json_output = r::table("users")
+		.fold(0, |acc, _user| acc + 1)
+		.emit(|_acc, user, new_acc| r::array().push(user.merge(r::object().insert("n", new_acc))))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[187,[[15,[\"users\"]],0,[69,[[2,[3,4]],[24,[[10,[3]],1]]]]],{\"emit\":[69,[[2,[5,6,7]],[2,[[35,[[10,[6]],{\"n\":[10,[7]]}]]]]]]}]");
+	
+	let json_output = r::table("users").max_index("age").update(r::object().insert("oldest", true)).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[53,[[148,[[15,[\"users\"]]],{\"index\":\"age\"}],{\"oldest\":true}]]");
+}
+
+#[test]
+fn test_join_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::table("orders")
+		.eq_join("customer_id", r::table("customers"))
+		.index("by_id")
+		.ordered(true)
+		.zip()
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[72,[[50,[[15,[\"orders\"]],\"customer_id\",[15,[\"customers\"]]],{\"index\":\"by_id\",\"ordered\":true}]]]");
+	
+	let json_output = r::table("orders").eq_join_fn(|order| order.get_field("customer"), r::table("customers")).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[50,[[15,[\"orders\"]],[69,[[2,[0]],[31,[[10,[0]],\"customer\"]]]],[15,[\"customers\"]]]]");
+	
+	let json_output = r::table("orders")
+		.inner_join(r::table("customers"), |order, customer| order.get_field("total").gt(customer.get_field("limit")))
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[48,[[15,[\"orders\"]],[15,[\"customers\"]],[69,[[2,[1,2]],[21,[[31,[[10,[1]],\"total\"]],[31,[[10,[2]],\"limit\"]]]]]]]]");
+	
+	let json_output = r::table("orders").outer_join(r::table("customers"), |_order, _customer| true).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[49,[[15,[\"orders\"]],[15,[\"customers\"]],[69,[[2,[3,4]],true]]]]");
+}
+
+#[test]
+fn test_write_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::table("users")
+		.insert(r::object().insert("name", r::expr("Ann")))
+		.conflict(r::Conflict::Replace)
+		.return_changes(true)
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[56,[[15,[\"users\"]],{\"name\":\"Ann\"}],{\"conflict\":\"replace\",\"return_changes\":true}]");
+	
+	let json_output = r::table("users")
+		.insert(r::object().insert("name", r::expr("Ann")))
+		.conflict_fn(|_id, old_doc, _new_doc| old_doc)
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[56,[[15,[\"users\"]],{\"name\":\"Ann\"}],{\"conflict\":[69,[[2,[0,1,2]],[10,[1]]]]}]");
+	
+	let json_output = r::table("users")
+		.update(r::object().insert("active", false))
+		.durability(r::Durability::Soft)
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[53,[[15,[\"users\"]],{\"active\":false}],{\"durability\":\"soft\"}]");
+	
+	let json_output = r::table("users").delete().get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[54,[[15,[\"users\"]]]]");
+}
+
+#[test]
+fn test_select_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::table("users").get(r::expr("ann")).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[16,[[15,[\"users\"]],\"ann\"]]");
+	
+	let json_output = r::table("users").get_all(vec![r::expr("ann"), r::expr("bob")]).index("name").get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[78,[[15,[\"users\"]],\"ann\",\"bob\"],{\"index\":\"name\"}]");
+	
+	let json_output = r::table("users")
+		.between(r::minval(), 30)
+		.index("age")
+		.right_bound(r::Bound::Closed)
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[182,[[15,[\"users\"]],[180,[]],30],{\"index\":\"age\",\"right_bound\":\"closed\"}]");
+}
+
+#[test]
+fn test_changes_json() {
+	let mut state = r::ReQLGenState::new();
+	
+	let json_output = r::table("users")
+		.changes()
+		.include_initial(true)
+		.squash_secs(0.5)
+		.get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[152,[[15,[\"users\"]]],{\"include_initial\":true,\"squash\":0.5}]");
+	
+	let json_output = r::table("users").get(r::expr("ann")).changes().include_types(true).get_reql_json(&mut state);
+	assert_eq!(format!("{}", json_output), "[152,[[16,[[15,[\"users\"]],\"ann\"]]],{\"include_types\":true}]");
+}
+
+#[test]
+fn test_default_db() {
+	let conn = Arc::new(r::connection().dbname(String::from("task_planner")).connect().unwrap());
+	assert_eq!(conn.default_db(), "task_planner");
+	
+	r::table("tasks").run(&conn).unwrap();
+	
+	//shared connections can switch databases too
+	conn.use_db(String::from("rethinkdb"));
+	r::table("server_status").run(&conn).unwrap();
+}
+
+#[test]
+fn test_noreply() {
+	let conn = r::connection().dbname(String::from("task_planner")).connect().unwrap();
+	
+	for _ in 0..10 {
+		r::table("tasks").run_noreply(&conn).unwrap();
+	}
+	conn.noreply_wait().unwrap();
+	
+	//the discarded responses do not get in the way of later queries
+	r::table("tasks").run(&conn).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+struct Task {
+	id: String,
+	title: String,
+	done: bool,
+}
+
+#[test]
+fn test_run_as() {
+	let conn = r::connection().dbname(String::from("task_planner")).connect().unwrap();
+	let task = Task {id: String::from("run-as-test"), title: String::from("Round trip"), done: false};
+	
+	let result = r::table("tasks")
+		.insert(r::expr(&task))
+		.conflict(r::Conflict::Replace)
+		.return_changes(true)
+		.run_write_as::<Task, _>(&conn)
+		.unwrap();
+	assert_eq!(result.changes[0].new_val, Some(task.clone()));
+	
+	let tasks = r::table("tasks")
+		.get_all(vec![r::expr("run-as-test")])
+		.run_as::<Task, _>(&conn)
+		.unwrap()
+		.collect::<Result<Vec<_>, _>>()
+		.unwrap();
+	assert_eq!(tasks, vec![task]);
+	
+	r::table("tasks").get(r::expr("run-as-test")).delete().run_write(&conn).unwrap();
+}
+
+#[test]
+fn test_changes_feed() {
+	let conn = r::connection().dbname(String::from("task_planner")).connect().unwrap();
+	let writer = r::connection().dbname(String::from("task_planner")).connect().unwrap();
+	
+	let mut feed = r::table("tasks")
+		.get(r::expr("feed-test"))
+		.changes()
+		.include_types(true)
+		.run_feed_as::<Task, _>(&conn)
+		.unwrap();
+	
+	let task = Task {id: String::from("feed-test"), title: String::from("Watched"), done: false};
+	r::table("tasks").insert(r::expr(&task)).run_write(&writer).unwrap();
+	
+	let change = feed.next().unwrap().unwrap();
+	assert_eq!(change.change_type, Some(r::ChangeType::Add));
+	assert_eq!(change.new_val, Some(task));
+	
+	feed.close().unwrap();
+	r::table("tasks").get(r::expr("feed-test")).delete().run_write(&writer).unwrap();
+}
+
+#[test]
+fn test_resumable_feed() {
+	let conn = r::connection().dbname(String::from("task_planner")).reconnecting().build().unwrap();
+	
+	let mut feed = r::table("tasks")
+		.get(r::expr("resumable-test"))
+		.changes()
+		.run_resumable_as::<Task>(&conn)
+		.unwrap();
+	
+	let task = Task {id: String::from("resumable-test"), title: String::from("Resumed"), done: false};
+	r::table("tasks").insert(r::expr(&task)).run_write(&*conn.get().unwrap()).unwrap();
+	
+	match feed.next().unwrap().unwrap() {
+		r::FeedEvent::Change(change) => assert_eq!(change.new_val, Some(task)),
+		r::FeedEvent::Resynced => panic!("the connection was not dropped"),
+	}
+	
+	feed.close().unwrap();
+	assert!(feed.next().is_none());
+	r::table("tasks").get(r::expr("resumable-test")).delete().run_write(&*conn.get().unwrap()).unwrap();
+}
+
+#[test]
+fn test_run_grouped() {
+	let conn = r::connection().dbname(String::from("task_planner")).connect().unwrap();
+	
+	let counts = r::table("tasks").group(vec!["done"]).count().run_grouped::<bool, u64, _>(&conn).unwrap();
+	let total = r::table("tasks").count().run(&conn).unwrap().next().unwrap().unwrap();
+	assert_eq!(counts.values().sum::<u64>(), total.as_u64().unwrap());
+}
+
+#[test]
+fn test_run_join() {
+	let conn = r::connection().dbname(String::from("task_planner")).connect().unwrap();
+	
+	//every task matches itself by primary key
+	let pairs = r::table("tasks")
+		.eq_join("id", r::table("tasks"))
+		.run_as::<r::JoinPair<Task, Task>, _>(&conn)
+		.unwrap()
+		.collect::<Result<Vec<_>, _>>()
+		.unwrap();
+	assert!(pairs.iter().all(|pair| pair.left == pair.right));
+	
+	//an outer join keeps tasks that match nothing
+	let pairs = r::table("tasks")
+		.outer_join(r::table("tasks"), |_left, _right| false)
+		.run_as::<r::JoinPair<Task, Option<Task>>, _>(&conn)
+		.unwrap()
+		.collect::<Result<Vec<_>, _>>()
+		.unwrap();
+	assert!(pairs.iter().all(|pair| pair.right.is_none()));
+}
+
+#[test]
+fn test_pool_checkout() {
+	let pool = r::connection()
+		.dbname(String::from("task_planner"))
+		.pool()
+		.max_size(2)
+		.checkout_timeout(Duration::from_secs(2))
+		.build()
+		.unwrap();
+	
+	let workers = (0..4).map(|_| {
+		let pool = pool.clone();
+		thread::spawn(move || {
+			let conn = pool.get().unwrap();
+			r::table("tasks").run(&*conn).unwrap().count()
+		})
+	}).collect::<Vec<_>>();
+	
+	for worker in workers {
+		worker.join().unwrap();
+	}
+	assert!(pool.connections() <= 2);
+	
+	//settings changed during a checkout do not carry over to the next one
+	pool.get().unwrap().use_db(String::from("other"));
+	assert_eq!(pool.get().unwrap().default_db(), "task_planner");
+	
+	let _a = pool.get().unwrap();
+	let _b = pool.get().unwrap();
+	match pool.get() {
+		Err(r::err::ConnectionError::Timeout) => (),
+		_ => panic!("expected the checkout to time out"),
+	}
+}